@@ -0,0 +1,19 @@
+#!/bin/sh
+# Vendors the latest v0.7.x release of The Graph's networks registry as the
+# snapshot bundled with poipal (src/client/networks_registry_snapshot.json).
+# Run before a release and commit the result.
+set -eu
+
+URL="https://networks-registry.thegraph.com/TheGraphNetworksRegistry_v0_7_x.json"
+OUT="$(dirname "$0")/../src/client/networks_registry_snapshot.json"
+
+curl -fsSL "$URL" -o "$OUT.tmp"
+# Refuse anything that is not a registry, e.g. a proxy error page
+if ! jq -e '.version and (.networks | length > 0)' "$OUT.tmp" > /dev/null; then
+    rm -f "$OUT.tmp"
+    echo "Downloaded file is not a networks registry" >&2
+    exit 1
+fi
+mv "$OUT.tmp" "$OUT"
+
+echo "Vendored networks registry $(jq -r .version "$OUT") ($(jq '.networks | length' "$OUT") networks)"
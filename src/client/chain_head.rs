@@ -0,0 +1,166 @@
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::client::eth::EthClient;
+use crate::client::poi::POIClient;
+use crate::client::{fetch_chain_heads_from_indexers, redact_url};
+use crate::models::{DeploymentId, Indexer};
+
+/// A source for the head block of a subgraph's network.
+pub enum ChainHeadProvider<'a> {
+    /// `eth_getBlockByNumber` on an EVM JSON-RPC endpoint
+    Rpc(EthClient),
+    /// The median `chainHeadBlock` reported by the status APIs of
+    /// `indexers`, which are allocated to `deployment`, taking the higher of
+    /// the two middle heads for an even count. Works for any chain
+    /// graph-node indexes, EVM or not.
+    Indexers {
+        indexers: &'a BTreeMap<String, Indexer>,
+        deployment: &'a DeploymentId,
+        network: &'a str,
+    },
+}
+
+impl ChainHeadProvider<'_> {
+    pub async fn chain_head(&self) -> Result<u32> {
+        match self {
+            Self::Rpc(eth_client) => eth_client.get_chain_head_block_number().await,
+            Self::Indexers {
+                indexers,
+                deployment,
+                network,
+            } => {
+                let heads = fetch_chain_heads_from_indexers(
+                    POIClient::new()?,
+                    indexers,
+                    deployment.ipfs_hash(),
+                    network,
+                )
+                .await;
+
+                let mut heads: Vec<u32> = heads.into_values().filter_map(Result::ok).collect();
+                if heads.is_empty() {
+                    return Err(anyhow!(
+                        "None of {} indexers reported a chain head for {}",
+                        indexers.len(),
+                        network
+                    ));
+                }
+                heads.sort_unstable();
+                Ok(heads[heads.len() / 2])
+            }
+        }
+    }
+}
+
+impl fmt::Display for ChainHeadProvider<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rpc(eth_client) => write!(f, "RPC {}", redact_url(eth_client.url())),
+            Self::Indexers { .. } => write!(f, "median indexer chain head"),
+        }
+    }
+}
+
+/// Reads the chain head from the first of `providers` that answers, and
+/// returns it with the provider used.
+pub async fn fetch_chain_head<'a, 'b>(
+    providers: &'b [ChainHeadProvider<'a>],
+) -> Result<(u32, &'b ChainHeadProvider<'a>)> {
+    let mut errors = Vec::new();
+    for provider in providers {
+        match provider.chain_head().await {
+            Ok(block) => return Ok((block, provider)),
+            Err(e) => errors.push(format!("{}: {}", provider, e)),
+        }
+    }
+
+    Err(anyhow!(
+        "Could not determine the chain head:\n  {}",
+        errors.join("\n  ")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::stub::StubServer;
+    use crate::models::AllocationStatus;
+    use serde_json::json;
+
+    const DEPLOYMENT: &str = "QmaS7sZNgzqij8FKb7VPK4kppqqsgzujogKDTjTkAcn59j";
+
+    /// One stub indexer per head, reporting it as the `mainnet` chain head;
+    /// `None` answers with an HTTP error instead.
+    fn indexers_reporting(heads: &[Option<u32>]) -> (Vec<StubServer>, BTreeMap<String, Indexer>) {
+        let mut servers = Vec::new();
+        let mut indexers = BTreeMap::new();
+        for (i, head) in heads.iter().enumerate() {
+            let server = match head {
+                Some(head) => {
+                    let body = json!({ "data": { "indexingStatuses": [{ "chains": [
+                        { "network": "mainnet", "chainHeadBlock": { "number": head.to_string() } }
+                    ] }] } });
+                    StubServer::start(200, Box::leak(body.to_string().into_boxed_str()))
+                }
+                None => StubServer::start(500, ""),
+            };
+            let id = format!("0x{}", i);
+            let indexer = Indexer {
+                id: id.clone(),
+                url: server.url.clone(),
+                staked_tokens: None,
+                allocation_ids: Vec::new(),
+                allocation_status: AllocationStatus::Active,
+            };
+            servers.push(server);
+            indexers.insert(id, indexer);
+        }
+        (servers, indexers)
+    }
+
+    async fn median_head(heads: &[Option<u32>]) -> Result<u32> {
+        let (_servers, indexers) = indexers_reporting(heads);
+        let deployment: DeploymentId = DEPLOYMENT.parse().unwrap();
+        ChainHeadProvider::Indexers {
+            indexers: &indexers,
+            deployment: &deployment,
+            network: "mainnet",
+        }
+        .chain_head()
+        .await
+    }
+
+    #[tokio::test]
+    async fn median_of_an_odd_count_is_the_middle_head() {
+        let heads = [Some(300), Some(100), None, Some(200)];
+        assert_eq!(median_head(&heads).await.unwrap(), 200);
+    }
+
+    #[tokio::test]
+    async fn median_of_an_even_count_is_the_higher_middle_head() {
+        let heads = [Some(400), Some(100), Some(300), Some(200)];
+        assert_eq!(median_head(&heads).await.unwrap(), 300);
+    }
+
+    #[tokio::test]
+    async fn indexers_that_all_fail_report_no_head() {
+        let error = median_head(&[None, None]).await.unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "None of 2 indexers reported a chain head for mainnet"
+        );
+    }
+
+    #[test]
+    fn rpc_providers_display_redacted_urls() {
+        let eth_client =
+            EthClient::new("https://mainnet.infura.io/v3/0123456789abcdef0123456789abcdef".into())
+                .unwrap();
+        assert_eq!(
+            ChainHeadProvider::Rpc(eth_client).to_string(),
+            "RPC https://mainnet.infura.io/v3/REDACTED"
+        );
+    }
+}
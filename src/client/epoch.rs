@@ -0,0 +1,312 @@
+use anyhow::{anyhow, Result};
+use reqwest::Client;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::json;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::client::subgraph::{GraphNetwork, GATEWAY_SUBGRAPHS_URL};
+use crate::client::{post_graphql, QueryRecorder};
+use crate::models::GraphQLQuery;
+
+const LATEST_EPOCH_QUERY: &str = r#"
+    query LatestEpoch {
+        globalState(id: "0") {
+            latestValidEpoch {
+                id
+            }
+        }
+    }
+"#;
+
+const EPOCH_BLOCKS_QUERY: &str = r#"
+    query EpochBlocks($epoch: ID!) {
+        epoch(id: $epoch) {
+            id
+            blockNumbers {
+                blockNumber
+                network {
+                    id
+                    alias
+                }
+            }
+        }
+    }
+"#;
+
+/// An epoch given on the command line: a number, or `current`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EpochSelector {
+    Number(u64),
+    Current,
+}
+
+impl FromStr for EpochSelector {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if value.eq_ignore_ascii_case("current") {
+            return Ok(EpochSelector::Current);
+        }
+        value
+            .parse()
+            .map(EpochSelector::Number)
+            .map_err(|_| format!("Invalid epoch '{}': expected a number or 'current'", value))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct LatestEpochData {
+    #[serde(rename = "globalState")]
+    global_state: Option<GlobalState>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GlobalState {
+    #[serde(rename = "latestValidEpoch")]
+    latest_valid_epoch: Option<EpochId>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EpochId {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct EpochBlocksData {
+    epoch: Option<EpochBlocks>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EpochBlocks {
+    #[serde(rename = "blockNumbers")]
+    block_numbers: Vec<NetworkEpochBlock>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NetworkEpochBlock {
+    #[serde(rename = "blockNumber")]
+    block_number: String,
+    network: OracleNetwork,
+}
+
+#[derive(Debug, Deserialize)]
+struct OracleNetwork {
+    /// CAIP-2 chain ID, e.g. `eip155:1`
+    id: String,
+    alias: String,
+}
+
+/// Client for the Epoch Block Oracle subgraph, which records the first block
+/// of every protocol epoch on each supported chain.
+pub struct EpochOracleClient {
+    client: Client,
+    url: String,
+    api_key: Option<String>,
+    recorder: Option<QueryRecorder>,
+}
+
+impl EpochOracleClient {
+    pub fn new(url: String, api_key: Option<String>) -> Result<Self> {
+        let client = Client::builder().timeout(Duration::from_secs(30)).build()?;
+        Ok(Self {
+            client,
+            url,
+            api_key,
+            recorder: None,
+        })
+    }
+
+    /// Records every query sent by this client in `recorder`.
+    pub fn with_recorder(mut self, recorder: QueryRecorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Creates a client for the Epoch Block Oracle subgraph of `network`
+    /// through the gateway.
+    pub fn for_network(network: GraphNetwork, api_key: String) -> Result<Self> {
+        let subgraph_id = network.epoch_oracle_subgraph_id().ok_or_else(|| {
+            anyhow!(
+                "No Epoch Block Oracle preset for {:?}; use --epoch-oracle-url",
+                network
+            )
+        })?;
+        Self::new(
+            format!("{}/{}", GATEWAY_SUBGRAPHS_URL, subgraph_id),
+            Some(api_key),
+        )
+    }
+
+    /// The latest epoch for which the oracle has recorded blocks.
+    pub async fn latest_epoch(&self) -> Result<u64> {
+        let query = GraphQLQuery {
+            query: LATEST_EPOCH_QUERY,
+            variables: json!({}),
+        };
+        let data: LatestEpochData = self.query(query).await?;
+        data.global_state
+            .and_then(|state| state.latest_valid_epoch)
+            .ok_or_else(|| anyhow!("Epoch Block Oracle has no valid epoch yet"))?
+            .id
+            .parse()
+            .map_err(|e| anyhow!("Invalid epoch ID from Epoch Block Oracle: {}", e))
+    }
+
+    /// Resolves `epoch` to its number and its first block on `network`.
+    /// `network` is matched against the oracle's network alias or, when
+    /// `caip2_id` is given, its CAIP-2 chain ID.
+    pub async fn epoch_start_block(
+        &self,
+        epoch: EpochSelector,
+        network: &str,
+        caip2_id: Option<&str>,
+    ) -> Result<(u64, u32)> {
+        let epoch = match epoch {
+            EpochSelector::Number(epoch) => epoch,
+            EpochSelector::Current => self.latest_epoch().await?,
+        };
+
+        let query = GraphQLQuery {
+            query: EPOCH_BLOCKS_QUERY,
+            variables: json!({ "epoch": epoch.to_string() }),
+        };
+        let data: EpochBlocksData = self.query(query).await?;
+        let blocks = data
+            .epoch
+            .ok_or_else(|| anyhow!("Epoch {} not found in Epoch Block Oracle", epoch))?
+            .block_numbers;
+
+        let block = blocks
+            .iter()
+            .find(|block| block.network.alias == network)
+            .or_else(|| {
+                caip2_id
+                    .and_then(|caip2_id| blocks.iter().find(|block| block.network.id == caip2_id))
+            })
+            .ok_or_else(|| {
+                anyhow!(
+                    "Epoch Block Oracle has no block for network '{}' at epoch {}",
+                    network,
+                    epoch
+                )
+            })?;
+
+        let block_number = block
+            .block_number
+            .parse()
+            .map_err(|e| anyhow!("Invalid block number from Epoch Block Oracle: {}", e))?;
+
+        Ok((epoch, block_number))
+    }
+
+    async fn query<T: DeserializeOwned>(&self, query: GraphQLQuery) -> Result<T> {
+        post_graphql(
+            &self.client,
+            &self.url,
+            self.api_key.as_deref(),
+            &query,
+            "Epoch Block Oracle",
+            self.recorder.as_ref(),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::stub::StubServer;
+
+    const EPOCH_BLOCKS: &str = r#"{"data":{"epoch":{"id":"901","blockNumbers":[
+        {"blockNumber":"21000000","network":{"id":"eip155:1","alias":"mainnet"}},
+        {"blockNumber":"280000000","network":{"id":"eip155:42161","alias":"arbitrum-one"}}
+    ]}}}"#;
+
+    fn client(server: &StubServer) -> EpochOracleClient {
+        EpochOracleClient::new(server.url.clone(), None).unwrap()
+    }
+
+    fn variables(request: &str) -> serde_json::Value {
+        serde_json::from_str::<serde_json::Value>(request).unwrap()["variables"].clone()
+    }
+
+    #[tokio::test]
+    async fn resolves_an_epoch_number() {
+        let server = StubServer::start(200, EPOCH_BLOCKS);
+        let resolved = client(&server)
+            .epoch_start_block(EpochSelector::Number(901), "mainnet", None)
+            .await
+            .unwrap();
+        assert_eq!(resolved, (901, 21_000_000));
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(variables(&requests[0]), json!({ "epoch": "901" }));
+    }
+
+    #[tokio::test]
+    async fn resolves_the_current_epoch() {
+        let server = StubServer::with_responses(vec![
+            (
+                200,
+                r#"{"data":{"globalState":{"latestValidEpoch":{"id":"901"}}}}"#,
+            ),
+            (200, EPOCH_BLOCKS),
+        ]);
+        let resolved = client(&server)
+            .epoch_start_block(EpochSelector::Current, "mainnet", None)
+            .await
+            .unwrap();
+        assert_eq!(resolved, (901, 21_000_000));
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].contains("LatestEpoch"));
+        assert_eq!(variables(&requests[1]), json!({ "epoch": "901" }));
+    }
+
+    #[tokio::test]
+    async fn matches_networks_by_alias_then_caip2_id() {
+        let server = StubServer::start(200, EPOCH_BLOCKS);
+        let client = client(&server);
+
+        let by_alias = client
+            .epoch_start_block(EpochSelector::Number(901), "arbitrum-one", Some("eip155:1"))
+            .await
+            .unwrap();
+        assert_eq!(by_alias.1, 280_000_000);
+
+        let by_caip2 = client
+            .epoch_start_block(EpochSelector::Number(901), "arbitrum", Some("eip155:42161"))
+            .await
+            .unwrap();
+        assert_eq!(by_caip2.1, 280_000_000);
+
+        let error = client
+            .epoch_start_block(EpochSelector::Number(901), "base", Some("eip155:8453"))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("no block for network 'base'"));
+    }
+
+    #[tokio::test]
+    async fn unknown_epochs_are_an_error() {
+        let server = StubServer::start(200, r#"{"data":{"epoch":null}}"#);
+        let error = client(&server)
+            .epoch_start_block(EpochSelector::Number(99999), "mainnet", None)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Epoch 99999 not found in Epoch Block Oracle"
+        );
+    }
+
+    #[test]
+    fn parses_epoch_selectors() {
+        assert_eq!("42".parse(), Ok(EpochSelector::Number(42)));
+        assert_eq!("Current".parse(), Ok(EpochSelector::Current));
+        assert!("latest".parse::<EpochSelector>().is_err());
+    }
+}
@@ -2,12 +2,19 @@ use std::time::Duration;
 
 use anyhow::{anyhow, Result};
 use reqwest::Client;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
 #[derive(Debug, Serialize, Deserialize)]
-struct JsonRpcResponse {
-    result: Option<BlockResult>,
+struct JsonRpcResponse<T> {
+    result: Option<T>,
+    error: Option<JsonRpcError>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct JsonRpcError {
+    message: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -22,32 +29,113 @@ pub struct EthClient {
 
 impl EthClient {
     pub fn new(url: String) -> Result<Self> {
-        let client = Client::builder().timeout(Duration::from_secs(10)).build()?;
+        let client = Client::builder()
+            .connect_timeout(Duration::from_secs(5))
+            .timeout(Duration::from_secs(10))
+            .build()?;
 
         Ok(Self { client, url })
     }
 
+    /// Returns a client for the first of `urls` that serves blocks and, when
+    /// `chain_id` is given, that chain. Block reads are probed because some
+    /// proxies answer `eth_chainId` statically while failing everything else.
+    pub async fn connect(urls: &[String], chain_id: Option<u64>) -> Result<Self> {
+        let mut errors = Vec::new();
+        for url in urls {
+            let client = Self::new(url.clone())?;
+            match (client.chain_id().await, chain_id) {
+                (Ok(actual), Some(expected)) if actual != expected => errors.push(format!(
+                    "{}: serves chain {}, expected {}",
+                    url, actual, expected
+                )),
+                (Ok(_), _) => match client.get_chain_head_block_number().await {
+                    Ok(_) => return Ok(client),
+                    Err(e) => errors.push(format!("{}: {}", url, e)),
+                },
+                (Err(e), _) => errors.push(format!("{}: {}", url, e)),
+            }
+        }
+
+        Err(anyhow!("No working RPC URL:\n  {}", errors.join("\n  ")))
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub async fn chain_id(&self) -> Result<u64> {
+        let chain_id: String = self.request("eth_chainId", json!([])).await?;
+        parse_quantity(&chain_id)
+    }
+
     pub async fn get_chain_head_block_number(&self) -> Result<u32> {
+        let block: BlockResult = self
+            .request("eth_getBlockByNumber", json!(["latest", false]))
+            .await?;
+
+        Ok(u32::try_from(parse_quantity(&block.number)?)?)
+    }
+
+    async fn request<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<T> {
         let body = json!({
             "jsonrpc": "2.0",
-            "method": "eth_getBlockByNumber",
-            "params": ["latest", false],
+            "method": method,
+            "params": params,
             "id": 1
         });
 
         let response = self.client.post(&self.url).json(&body).send().await?;
-        let json_response: JsonRpcResponse = response.json().await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(anyhow!("HTTP {}", status));
+        }
+        let json_response: JsonRpcResponse<T> = response.json().await?;
 
-        let block = json_response
+        if let Some(error) = json_response.error {
+            return Err(anyhow!("JSON-RPC error: {}", error.message));
+        }
+        json_response
             .result
-            .ok_or_else(|| anyhow!("No result in JSON-RPC response"))?;
+            .ok_or_else(|| anyhow!("No result in JSON-RPC response"))
+    }
+}
 
-        let block_number = if block.number.starts_with("0x") {
-            u32::from_str_radix(&block.number[2..], 16)?
-        } else {
-            block.number.parse::<u32>()?
-        };
+/// Parses a JSON-RPC quantity, hex (`0x...`) or decimal.
+fn parse_quantity(value: &str) -> Result<u64> {
+    let parsed = match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    parsed.map_err(|_| anyhow!("Invalid quantity '{}' in JSON-RPC response", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::stub::StubServer;
+
+    #[test]
+    fn parses_hex_and_decimal_quantities() {
+        assert_eq!(parse_quantity("0x1").unwrap(), 1);
+        assert_eq!(parse_quantity("0x89").unwrap(), 137);
+        assert_eq!(parse_quantity("42").unwrap(), 42);
+        assert!(parse_quantity("0xzz").is_err());
+    }
 
-        Ok(block_number)
+    #[tokio::test]
+    async fn endpoint_answering_only_chain_id_is_skipped() {
+        // Answers every call with a chain ID, so block reads fail to parse
+        let proxy = StubServer::start(200, r#"{"jsonrpc":"2.0","id":1,"result":"0x1"}"#);
+        let error = EthClient::connect(std::slice::from_ref(&proxy.url), Some(1))
+            .await
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains(&proxy.url));
+        assert_eq!(proxy.requests().len(), 2);
     }
 }
@@ -1,43 +1,440 @@
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
 use std::time::Duration;
 
-use anyhow::Result;
-use regex::Regex;
+use anyhow::{anyhow, Result};
+use reqwest::header::ACCEPT;
 use reqwest::Client;
+use std::collections::BTreeSet;
+
+use crate::models::{
+    decode_file_node, module_initial_block, single_network, verify_block, DataSource, LineageEntry,
+    SubgraphManifest,
+};
+
+/// Graft chains longer than this are treated as an error
+const MAX_GRAFT_DEPTH: usize = 32;
+
+/// Deepest chain of links followed within one file
+const MAX_FILE_DEPTH: usize = 16;
+
+/// Largest file fetched; manifests and substreams packages are far smaller
+const MAX_FILE_SIZE: usize = 64 * 1024 * 1024;
+
+/// The IPFS proxy poipal used before endpoints could be listed. Given without
+/// a path it still means its API, as in `--ipfs-url https://ipfs.thegraph.com`.
+const THEGRAPH_IPFS_URL: &str = "https://ipfs.thegraph.com";
+
+/// Where raw IPFS blocks are fetched from. URLs ending in `/api/v0` are IPFS
+/// (Kubo) API endpoints; anything else is treated as a path gateway.
+#[derive(Debug, Clone)]
+pub enum IpfsEndpoint {
+    Api(String),
+    Gateway(String),
+}
+
+impl FromStr for IpfsEndpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let url = s.trim().trim_end_matches('/');
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(format!("Invalid IPFS URL '{}': expected http(s)://...", s));
+        }
+        if url.ends_with("/api/v0") {
+            Ok(Self::Api(url.to_string()))
+        } else if url == THEGRAPH_IPFS_URL {
+            Ok(Self::Api(format!("{}/ipfs/api/v0", url)))
+        } else {
+            Ok(Self::Gateway(url.to_string()))
+        }
+    }
+}
+
+impl fmt::Display for IpfsEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Api(url) | Self::Gateway(url) => write!(f, "{}", url),
+        }
+    }
+}
 
 pub struct IpfsClient {
     client: Client,
-    url: String,
+    endpoints: Vec<IpfsEndpoint>,
 }
 
 impl IpfsClient {
-    pub fn new(url: String) -> Result<Self> {
+    pub fn new(endpoints: Vec<IpfsEndpoint>) -> Result<Self> {
+        if endpoints.is_empty() {
+            return Err(anyhow!("At least one IPFS URL is required"));
+        }
         let client = Client::builder().timeout(Duration::from_secs(30)).build()?;
-        Ok(Self { client, url })
+        Ok(Self { client, endpoints })
     }
 
-    pub async fn fetch_manifest(&self, hash: &str) -> Result<String> {
-        let url = format!("{}/ipfs/api/v0/cat?arg={}", self.url, hash);
-        let response = self.client.get(url).send().await?;
-        let body = response.text().await?;
-        Ok(body)
+    /// Fetches the contents of `hash`, trying each endpoint in order. Every
+    /// block is checked against its hash, so a broken or malicious endpoint
+    /// can only fail the fetch, never change what it returns.
+    pub async fn fetch_file(&self, hash: &str) -> Result<Vec<u8>> {
+        let mut errors = Vec::new();
+        for endpoint in &self.endpoints {
+            match self.fetch_file_from(endpoint, hash).await {
+                Ok(content) => return Ok(content),
+                Err(e) => errors.push(format!("{}: {}", endpoint, e)),
+            }
+        }
+
+        Err(anyhow!(
+            "Failed to fetch {} from IPFS:\n  {}",
+            hash,
+            errors.join("\n  ")
+        ))
     }
 
-    pub async fn get_start_block(&self, manifest: &str) -> Result<u32> {
-        let re = Regex::new(r"startBlock:\s*(\d+)").unwrap();
-        let start_blocks: Vec<u32> = re
-            .captures_iter(manifest)
-            .filter_map(|cap| cap[1].parse::<u32>().ok())
+    async fn fetch_file_from(&self, endpoint: &IpfsEndpoint, hash: &str) -> Result<Vec<u8>> {
+        let mut content = Vec::new();
+        self.fetch_file_node(endpoint, hash, 0, &mut content)
+            .await?;
+        Ok(content)
+    }
+
+    /// Appends the content of the file node `hash` and its children to
+    /// `content`, bounding both the link depth and the total size.
+    async fn fetch_file_node(
+        &self,
+        endpoint: &IpfsEndpoint,
+        hash: &str,
+        depth: usize,
+        content: &mut Vec<u8>,
+    ) -> Result<()> {
+        if depth > MAX_FILE_DEPTH {
+            return Err(anyhow!("File links nest deeper than {}", MAX_FILE_DEPTH));
+        }
+
+        let block = self.fetch_block(endpoint, hash).await?;
+        let node = decode_file_node(&block)?;
+        if content.len() + node.data.len() > MAX_FILE_SIZE {
+            return Err(anyhow!("File is larger than {} bytes", MAX_FILE_SIZE));
+        }
+
+        content.extend_from_slice(node.data);
+        for link in &node.links {
+            Box::pin(self.fetch_file_node(endpoint, link, depth + 1, content)).await?;
+        }
+        Ok(())
+    }
+
+    /// Fetches the raw block of `hash` and verifies it.
+    async fn fetch_block(&self, endpoint: &IpfsEndpoint, hash: &str) -> Result<Vec<u8>> {
+        let request = match endpoint {
+            IpfsEndpoint::Api(url) => self.client.post(format!("{}/block/get?arg={}", url, hash)),
+            IpfsEndpoint::Gateway(url) => self
+                .client
+                .get(format!("{}/ipfs/{}?format=raw", url, hash))
+                .header(ACCEPT, "application/vnd.ipld.raw"),
+        };
+        let response = request.send().await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(anyhow!("HTTP {}", status));
+        }
+
+        let block = response.bytes().await?.to_vec();
+        verify_block(hash, &block)?;
+        Ok(block)
+    }
+
+    pub async fn fetch_manifest(&self, hash: &str) -> Result<SubgraphManifest> {
+        let body = self.fetch_file(hash).await?;
+        let body = String::from_utf8(body)
+            .map_err(|_| anyhow!("{} is not a subgraph manifest: not UTF-8 text", hash))?;
+        SubgraphManifest::from_yaml(&body).map_err(|e| anyhow!("{}: {}", hash, e))
+    }
+
+    /// The network `manifest` indexes. Composed subgraph data sources carry
+    /// no network of their own and take the one of their source deployment.
+    pub async fn resolve_network(&self, manifest: &SubgraphManifest) -> Result<String> {
+        let mut networks: BTreeSet<String> = manifest
+            .networks()
+            .into_iter()
+            .map(str::to_string)
             .collect();
 
-        Ok(start_blocks.iter().min().unwrap_or(&0).to_owned())
+        for data_source in &manifest.data_sources {
+            if !data_source.is_subgraph() || data_source.network.is_some() {
+                continue;
+            }
+            let source = Self::subgraph_source(data_source)?;
+            let source_manifest = self.fetch_manifest(source).await?;
+            networks.insert(Box::pin(self.resolve_network(&source_manifest)).await?);
+        }
+
+        single_network(networks)
+    }
+
+    /// The earliest block `manifest` indexes. Composed subgraph data sources
+    /// without a `startBlock` start where their source deployment does, and
+    /// substreams data sources start at their module's initial block.
+    pub async fn resolve_start_block(&self, manifest: &SubgraphManifest) -> Result<u32> {
+        let mut start_block: Option<u32> = None;
+
+        for data_source in &manifest.data_sources {
+            let block = match data_source.start_block() {
+                Some(block) => block,
+                None if data_source.is_subgraph() => {
+                    let source = Self::subgraph_source(data_source)?;
+                    let source_manifest = self.fetch_manifest(source).await?;
+                    Box::pin(self.resolve_start_block(&source_manifest)).await?
+                }
+                None if data_source.is_substreams() => {
+                    let package = data_source
+                        .source
+                        .as_ref()
+                        .and_then(|source| source.package.as_ref())
+                        .ok_or_else(|| anyhow!("Substreams data source has no package"))?;
+                    let spkg = self.fetch_file(package.file.hash()).await?;
+                    let block = module_initial_block(&spkg, &package.module_name)?;
+                    u32::try_from(block)
+                        .map_err(|_| anyhow!("Module initial block {} is out of range", block))?
+                }
+                None => 0,
+            };
+            start_block = Some(start_block.map_or(block, |start| start.min(block)));
+        }
+
+        Ok(start_block.unwrap_or(0))
+    }
+
+    fn subgraph_source(data_source: &DataSource) -> Result<&str> {
+        data_source
+            .source
+            .as_ref()
+            .and_then(|source| source.address.as_deref())
+            .ok_or_else(|| anyhow!("Subgraph data source has no source deployment"))
+    }
+
+    /// Fetches the manifest of `hash` and of every deployment it was grafted
+    /// onto, following `graft.base` until a deployment without a graft.
+    /// The first entry is `hash` itself.
+    pub async fn fetch_graft_lineage(&self, hash: &str) -> Result<Vec<LineageEntry>> {
+        follow_grafts(hash, |deployment| async move {
+            self.fetch_manifest(&deployment).await
+        })
+        .await
+    }
+}
+
+/// Walks the graft chain starting at `hash`, getting each manifest from
+/// `fetch`. Fails on chains that loop or exceed [`MAX_GRAFT_DEPTH`].
+async fn follow_grafts<F, Fut>(hash: &str, mut fetch: F) -> Result<Vec<LineageEntry>>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = Result<SubgraphManifest>>,
+{
+    let mut lineage: Vec<LineageEntry> = Vec::new();
+    let mut deployment = hash.to_string();
+
+    loop {
+        if lineage.iter().any(|entry| entry.deployment == deployment) {
+            return Err(anyhow!("Graft chain loops back to {}", deployment));
+        }
+        if lineage.len() >= MAX_GRAFT_DEPTH {
+            return Err(anyhow!(
+                "Graft chain of {} is longer than {} deployments",
+                hash,
+                MAX_GRAFT_DEPTH
+            ));
+        }
+
+        let manifest = fetch(deployment.clone()).await?;
+        let base = manifest.graft.as_ref().map(|graft| graft.base.clone());
+        lineage.push(LineageEntry {
+            deployment,
+            manifest,
+        });
+
+        match base {
+            Some(base) => deployment = base,
+            None => return Ok(lineage),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::stub::StubServer;
+    use sha2::{Digest, Sha256};
+    use std::collections::HashMap;
+
+    fn push_varint(out: &mut Vec<u8>, mut value: usize) {
+        while value >= 0x80 {
+            out.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+
+    /// Wraps `content` in a single UnixFS file block and returns its CIDv0.
+    fn file_block(content: &str) -> (String, Vec<u8>) {
+        let mut unixfs = vec![0x08, 0x02, 0x12];
+        push_varint(&mut unixfs, content.len());
+        unixfs.extend_from_slice(content.as_bytes());
+        unixfs.push(0x18);
+        push_varint(&mut unixfs, content.len());
+
+        let mut block = vec![0x0a];
+        push_varint(&mut block, unixfs.len());
+        block.extend(unixfs);
+
+        let mut multihash = vec![0x12, 0x20];
+        multihash.extend_from_slice(&Sha256::digest(&block));
+        (bs58::encode(multihash).into_string(), block)
+    }
+
+    /// Files served by a stub gateway, keyed by CID.
+    #[derive(Default)]
+    struct Files(HashMap<String, Vec<u8>>);
+
+    impl Files {
+        fn add(&mut self, content: &str) -> String {
+            let (cid, block) = file_block(content);
+            self.0.insert(cid.clone(), block);
+            cid
+        }
+
+        fn serve(self) -> (StubServer, IpfsClient) {
+            let server = StubServer::with_handler(move |path, _| {
+                let cid = path
+                    .trim_start_matches("/ipfs/")
+                    .trim_end_matches("?format=raw");
+                match self.0.get(cid) {
+                    Some(block) => (200, block.clone()),
+                    None => (404, Vec::new()),
+                }
+            });
+            let client = IpfsClient::new(vec![endpoint(&server.url)]).unwrap();
+            (server, client)
+        }
+    }
+
+    fn grafted_manifest(base: Option<&str>) -> String {
+        let mut manifest =
+            "specVersion: 1.0.0\ndataSources:\n  - kind: ethereum\n    network: mainnet\n"
+                .to_string();
+        if let Some(base) = base {
+            manifest.push_str(&format!("graft:\n  base: {}\n  block: 100\n", base));
+        }
+        manifest
     }
 
-    pub async fn get_network(&self, manifest: &str) -> Result<Option<String>> {
-        let re = Regex::new(r"network:\s*(\w+)").unwrap();
-        let networks: Vec<String> = re
-            .captures_iter(manifest)
-            .map(|cap| cap[1].to_string())
+    /// Adds a graft chain of `length` manifests, returning the CIDs from the
+    /// grafted deployment down to the root.
+    fn add_graft_chain(files: &mut Files, length: usize) -> Vec<String> {
+        let mut chain = vec![files.add(&grafted_manifest(None))];
+        for _ in 1..length {
+            let base = chain.last().unwrap().clone();
+            chain.push(files.add(&grafted_manifest(Some(&base))));
+        }
+        chain.reverse();
+        chain
+    }
+
+    #[tokio::test]
+    async fn composed_subgraphs_take_network_and_start_block_from_their_source() {
+        let mut files = Files::default();
+        let source = files.add(
+            "specVersion: 1.0.0\ndataSources:\n  \
+             - kind: ethereum\n    network: base\n    source:\n      startBlock: 7000\n  \
+             - kind: ethereum\n    network: base\n    source:\n      startBlock: 5000\n",
+        );
+        let composed = files.add(&format!(
+            "specVersion: 1.3.0\ndataSources:\n  \
+             - kind: subgraph\n    name: Composed\n    source:\n      address: '{}'\n",
+            source
+        ));
+        let (_server, client) = files.serve();
+
+        let manifest = client.fetch_manifest(&composed).await.unwrap();
+        assert!(manifest.networks().is_empty());
+        assert_eq!(client.resolve_network(&manifest).await.unwrap(), "base");
+        assert_eq!(client.resolve_start_block(&manifest).await.unwrap(), 5000);
+    }
+
+    #[tokio::test]
+    async fn graft_lineage_follows_bases_to_the_root() {
+        let mut files = Files::default();
+        let chain = add_graft_chain(&mut files, 3);
+        let (server, client) = files.serve();
+
+        let lineage = client.fetch_graft_lineage(&chain[0]).await.unwrap();
+        let deployments: Vec<_> = lineage
+            .iter()
+            .map(|entry| entry.deployment.clone())
             .collect();
-        Ok(networks.first().cloned())
+        assert_eq!(deployments, chain);
+        assert!(lineage[2].manifest.graft.is_none());
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn graft_lineage_is_bounded() {
+        let mut files = Files::default();
+        let chain = add_graft_chain(&mut files, MAX_GRAFT_DEPTH + 1);
+        let (server, client) = files.serve();
+
+        let error = client.fetch_graft_lineage(&chain[0]).await.unwrap_err();
+        assert!(error.to_string().contains("longer than 32 deployments"));
+        assert_eq!(server.requests().len(), MAX_GRAFT_DEPTH);
+
+        let lineage = client.fetch_graft_lineage(&chain[1]).await.unwrap();
+        assert_eq!(lineage.len(), MAX_GRAFT_DEPTH);
+    }
+
+    #[tokio::test]
+    async fn graft_loops_are_detected() {
+        // Content addressing rules loops out for verified manifests, so this
+        // walks manifests that are not fetched from IPFS
+        let manifests = HashMap::from([
+            ("QmA", grafted_manifest(Some("QmB"))),
+            ("QmB", grafted_manifest(Some("QmA"))),
+        ]);
+        let error = follow_grafts("QmA", |deployment| {
+            let manifest = SubgraphManifest::from_yaml(&manifests[deployment.as_str()]);
+            async move { manifest }
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(error.to_string(), "Graft chain loops back to QmA");
+    }
+
+    fn endpoint(url: &str) -> IpfsEndpoint {
+        url.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_api_and_gateway_urls() {
+        assert!(matches!(
+            endpoint("http://localhost:5001/api/v0/"),
+            IpfsEndpoint::Api(url) if url == "http://localhost:5001/api/v0"
+        ));
+        assert!(matches!(
+            endpoint("https://ipfs.io"),
+            IpfsEndpoint::Gateway(url) if url == "https://ipfs.io"
+        ));
+        assert!("ipfs.io".parse::<IpfsEndpoint>().is_err());
+    }
+
+    #[test]
+    fn legacy_thegraph_url_is_its_api() {
+        for url in ["https://ipfs.thegraph.com", "https://ipfs.thegraph.com/"] {
+            assert!(matches!(
+                endpoint(url),
+                IpfsEndpoint::Api(url) if url == "https://ipfs.thegraph.com/ipfs/api/v0"
+            ));
+        }
     }
 }
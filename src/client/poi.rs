@@ -1,73 +1,424 @@
-use anyhow::{anyhow, Result};
 use reqwest::{Client, Url};
+use serde::de::DeserializeOwned;
 use serde_json::json;
+use std::collections::HashMap;
+use std::fmt;
 use std::time::Duration;
 
-use crate::models::POIResponse;
+use crate::client::QueryRecorder;
+use crate::models::{GraphQLQuery, GraphQLResponse, IndexingStatusesData, POIData};
+
+const POIS_QUERY: &str = r#"
+    query PublicProofsOfIndexing($requests: [PublicProofOfIndexingRequest!]!) {
+        publicProofsOfIndexing(requests: $requests) {
+            deployment
+            proofOfIndexing
+            block {
+                number
+            }
+        }
+    }
+"#;
+
+/// Most requests graph-node's index node accepts in one
+/// `publicProofsOfIndexing` query; larger queries fail as too expensive
+const MAX_POI_REQUESTS: usize = 10;
+
+const INDEXING_STATUSES_QUERY: &str = r#"
+    query IndexingStatuses($subgraphs: [String!]!) {
+        indexingStatuses(subgraphs: $subgraphs) {
+            chains {
+                network
+                chainHeadBlock {
+                    number
+                }
+            }
+        }
+    }
+"#;
+
+/// Why a POI could not be fetched from an indexer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PoiError {
+    /// The indexer did not answer before the request timed out
+    Timeout,
+    /// The connection failed or was interrupted
+    Network(String),
+    /// The status endpoint answered with a non-success HTTP status
+    Http(u16),
+    /// graph-node answered with GraphQL errors
+    GraphQL(Vec<String>),
+    /// The indexer has no POI for this block: not synced that far, or history pruned
+    NotIndexed { deployment: String, block: u32 },
+    /// The response body is not a valid `publicProofsOfIndexing` response
+    Malformed(String),
+    /// The endpoint does not serve public POIs
+    UnsupportedEndpoint(String),
+    /// The indexer URL cannot be turned into a status endpoint
+    InvalidUrl(String),
+    /// The task fetching from the indexer panicked or was cancelled
+    Task(String),
+}
+
+impl PoiError {
+    /// Transient failures that may succeed when retried.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            PoiError::Timeout | PoiError::Network(_) => true,
+            PoiError::Http(status) => *status == 429 || *status >= 500,
+            _ => false,
+        }
+    }
+
+    /// Failures that will give the same answer on every attempt.
+    pub fn is_deterministic(&self) -> bool {
+        matches!(
+            self,
+            PoiError::NotIndexed { .. }
+                | PoiError::UnsupportedEndpoint(_)
+                | PoiError::InvalidUrl(_)
+        )
+    }
+}
+
+impl fmt::Display for PoiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoiError::Timeout => write!(f, "Request timed out"),
+            PoiError::Network(e) => write!(f, "Network error: {}", e),
+            PoiError::Http(status) => write!(f, "Failed to fetch POI: HTTP {}", status),
+            PoiError::GraphQL(messages) => write!(f, "GraphQL error: {}", messages.join("; ")),
+            PoiError::NotIndexed { block, .. } => {
+                write!(f, "Block {} not indexed (not synced or pruned)", block)
+            }
+            PoiError::Malformed(e) => write!(f, "Malformed response: {}", e),
+            PoiError::UnsupportedEndpoint(e) => write!(f, "Public POIs not supported: {}", e),
+            PoiError::InvalidUrl(e) => write!(f, "Invalid indexer URL: {}", e),
+            PoiError::Task(e) => write!(f, "Request failed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for PoiError {}
+
+impl From<reqwest::Error> for PoiError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            PoiError::Timeout
+        } else if e.is_decode() {
+            PoiError::Malformed(e.to_string())
+        } else {
+            PoiError::Network(e.to_string())
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct POIClient {
     client: Client,
+    recorder: Option<QueryRecorder>,
 }
 
 impl POIClient {
-    pub fn new() -> Result<Self> {
+    pub fn new() -> anyhow::Result<Self> {
         let client = Client::builder().timeout(Duration::from_secs(10)).build()?;
 
-        Ok(Self { client })
+        Ok(Self {
+            client,
+            recorder: None,
+        })
     }
 
-    pub async fn fetch_poi(&self, url: &str, deployment: &str, block: u32) -> Result<String> {
-        let url: Url = url.parse()?;
-        let status_url = url.join("status")?;
+    /// Records every query sent by this client in `recorder`.
+    pub fn with_recorder(mut self, recorder: QueryRecorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
 
-        let query = format!(
-            r#"{{ publicProofsOfIndexing(requests: [{{deployment: "{}", blockNumber: "{}"}}]) {{ deployment proofOfIndexing block {{ number }} }} }}"#,
-            deployment, block
-        );
+    pub async fn fetch_poi(
+        &self,
+        url: &str,
+        deployment: &str,
+        block: u32,
+    ) -> Result<String, PoiError> {
+        self.fetch_pois(url, &[(deployment, block)])
+            .await?
+            .remove(0)
+    }
+
+    /// Fetches POIs for many `(deployment, block)` pairs, batching up to
+    /// [`MAX_POI_REQUESTS`] of them per `publicProofsOfIndexing` query.
+    /// Returns one result per request, in request order.
+    pub async fn fetch_pois(
+        &self,
+        url: &str,
+        requests: &[(&str, u32)],
+    ) -> Result<Vec<Result<String, PoiError>>, PoiError> {
+        let mut results = Vec::with_capacity(requests.len());
+        for chunk in requests.chunks(MAX_POI_REQUESTS) {
+            results.extend(self.fetch_poi_batch(url, chunk).await?);
+        }
+        Ok(results)
+    }
+
+    /// Fetches POIs for at most [`MAX_POI_REQUESTS`] pairs in one query.
+    async fn fetch_poi_batch(
+        &self,
+        url: &str,
+        requests: &[(&str, u32)],
+    ) -> Result<Vec<Result<String, PoiError>>, PoiError> {
+        let request_list: Vec<serde_json::Value> = requests
+            .iter()
+            .map(|(deployment, block)| {
+                json!({ "deployment": deployment, "blockNumber": block.to_string() })
+            })
+            .collect();
+
+        let query = GraphQLQuery {
+            query: POIS_QUERY,
+            variables: json!({ "requests": request_list }),
+        };
+
+        let data: POIData = self
+            .post_status_query(url, &query)
+            .await
+            .map_err(|e| match e {
+                PoiError::Http(status @ (404 | 405)) => {
+                    PoiError::UnsupportedEndpoint(format!("HTTP {}", status))
+                }
+                PoiError::GraphQL(messages)
+                    if messages
+                        .iter()
+                        .any(|message| message.contains("publicProofsOfIndexing")) =>
+                {
+                    PoiError::UnsupportedEndpoint(messages.join("; "))
+                }
+                e => e,
+            })?;
+
+        let mut found: HashMap<(String, u32), String> = HashMap::new();
+        for poi in data.pois {
+            let (Some(deployment), Some(block), Some(proof)) = (poi.deployment, poi.block, poi.poi)
+            else {
+                continue;
+            };
+            if let Ok(number) = block.number.parse::<u32>() {
+                found.insert((deployment, number), proof);
+            }
+        }
+
+        Ok(requests
+            .iter()
+            .map(|(deployment, block)| {
+                found
+                    .remove(&(deployment.to_string(), *block))
+                    .ok_or_else(|| PoiError::NotIndexed {
+                        deployment: deployment.to_string(),
+                        block: *block,
+                    })
+            })
+            .collect())
+    }
+
+    /// The head block of `network` as seen by the indexer's graph-node,
+    /// taken from the indexing status of `deployment`.
+    pub async fn fetch_chain_head(
+        &self,
+        url: &str,
+        deployment: &str,
+        network: &str,
+    ) -> Result<u32, PoiError> {
+        let query = GraphQLQuery {
+            query: INDEXING_STATUSES_QUERY,
+            variables: json!({ "subgraphs": [deployment] }),
+        };
+
+        let data: IndexingStatusesData = self.post_status_query(url, &query).await?;
+        data.statuses
+            .into_iter()
+            .flat_map(|status| status.chains)
+            .find(|chain| chain.network == network)
+            .and_then(|chain| chain.chain_head_block)
+            .and_then(|block| block.number.parse().ok())
+            .ok_or_else(|| {
+                PoiError::Malformed(format!("No chain head reported for network {}", network))
+            })
+    }
+
+    /// Sends `query` to the status endpoint of the indexer at `url`. The
+    /// response is recorded before it is checked, so failures are kept too.
+    async fn post_status_query<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        query: &GraphQLQuery,
+    ) -> Result<T, PoiError> {
+        let url = Url::parse(url).map_err(|e| PoiError::InvalidUrl(e.to_string()))?;
+        let status_url = url
+            .join("status")
+            .map_err(|e| PoiError::InvalidUrl(e.to_string()))?;
 
         let response = self
             .client
-            .post(status_url)
-            .json(&json!({ "query": query }))
+            .post(status_url.clone())
+            .json(query)
             .send()
             .await?;
 
-        if !response.status().is_success() {
-            return Err(anyhow!("Failed to fetch POI: HTTP {}", response.status()));
+        let status = response.status();
+        let body = response.text().await?;
+        if let Some(recorder) = &self.recorder {
+            recorder.record(status_url.as_str(), query, status.as_u16(), &body);
         }
 
-        let mut poi_response: POIResponse = response.json().await?;
-
-        if poi_response.data.pois.is_empty() {
-            return Err(anyhow!("No POI found for block {}", block));
+        if !status.is_success() {
+            return Err(PoiError::Http(status.as_u16()));
         }
 
-        Ok(poi_response.data.pois.remove(0).poi)
+        let response: GraphQLResponse<T> =
+            serde_json::from_str(&body).map_err(|e| PoiError::Malformed(e.to_string()))?;
+        if !response.errors.is_empty() {
+            return Err(PoiError::GraphQL(response.error_messages()));
+        }
+        response
+            .data
+            .ok_or_else(|| PoiError::Malformed("Response has no data".to_string()))
     }
 
+    /// Retries transient failures only; see [`PoiError::is_retryable`].
     pub async fn fetch_poi_with_retry(
         &self,
         url: &str,
         deployment: &str,
         block: u32,
         max_retries: u32,
-    ) -> Result<String> {
-        let mut last_error = None;
-
-        for attempt in 1..=max_retries {
-            match self.fetch_poi(url, deployment, block).await {
-                Ok(poi) => return Ok(poi),
-                Err(e) => {
-                    last_error = Some(e);
-                    if attempt < max_retries {
-                        tokio::time::sleep(Duration::from_millis(500 * attempt as u64)).await;
-                    }
+    ) -> Result<String, PoiError> {
+        self.fetch_pois_with_retry(url, &[(deployment, block)], max_retries)
+            .await?
+            .remove(0)
+    }
+
+    /// Batched variant of [`fetch_poi_with_retry`](Self::fetch_poi_with_retry).
+    /// Only a failure of the whole request is retried; a missing POI for an
+    /// individual block is returned as-is.
+    pub async fn fetch_pois_with_retry(
+        &self,
+        url: &str,
+        requests: &[(&str, u32)],
+        max_retries: u32,
+    ) -> Result<Vec<Result<String, PoiError>>, PoiError> {
+        let mut attempt = 1;
+
+        loop {
+            match self.fetch_pois(url, requests).await {
+                Ok(pois) => return Ok(pois),
+                Err(e) if e.is_retryable() && attempt < max_retries => {
+                    tokio::time::sleep(Duration::from_millis(500 * attempt as u64)).await;
+                    attempt += 1;
                 }
+                Err(e) => return Err(e),
             }
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::stub::StubServer;
 
-        Err(last_error
-            .unwrap_or_else(|| anyhow!("Failed to fetch POI after {} retries", max_retries)))
+    #[tokio::test]
+    async fn failed_responses_are_recorded() {
+        let server = StubServer::start(503, "overloaded");
+        let recorder = QueryRecorder::new();
+        let client = POIClient::new().unwrap().with_recorder(recorder.clone());
+
+        let error = client.fetch_poi(&server.url, "Qm", 1).await.unwrap_err();
+        assert_eq!(error, PoiError::Http(503));
+
+        let queries = recorder.queries();
+        assert_eq!(queries.len(), 1);
+        assert_eq!(queries[0].status, 503);
+        assert_eq!(queries[0].response, "overloaded");
+    }
+
+    /// A `publicProofsOfIndexing` response with POI `0x<block>` for each
+    /// block, in reverse order as graph-node does not promise any order.
+    fn pois_response(blocks: impl DoubleEndedIterator<Item = u32>) -> &'static str {
+        let pois: Vec<_> = blocks
+            .rev()
+            .map(|block| {
+                json!({
+                    "deployment": "Qm",
+                    "proofOfIndexing": format!("0x{}", block),
+                    "block": { "number": block.to_string() },
+                })
+            })
+            .collect();
+        let body = json!({ "data": { "publicProofsOfIndexing": pois } });
+        Box::leak(body.to_string().into_boxed_str())
+    }
+
+    #[tokio::test]
+    async fn large_batches_are_split_and_kept_in_order() {
+        // Block 25 is not indexed, so the last batch omits it
+        let server = StubServer::with_responses(vec![
+            (200, pois_response(1..=10)),
+            (200, pois_response(11..=20)),
+            (200, pois_response(21..=24)),
+        ]);
+        let client = POIClient::new().unwrap();
+        let requests: Vec<(&str, u32)> = (1..=25).map(|block| ("Qm", block)).collect();
+
+        let results = client.fetch_pois(&server.url, &requests).await.unwrap();
+        assert_eq!(results.len(), 25);
+        for (i, result) in results[..24].iter().enumerate() {
+            assert_eq!(result, &Ok(format!("0x{}", i + 1)));
+        }
+        assert_eq!(
+            results[24],
+            Err(PoiError::NotIndexed {
+                deployment: "Qm".to_string(),
+                block: 25
+            })
+        );
+
+        let sent: Vec<usize> = server
+            .requests()
+            .iter()
+            .map(|request| {
+                let request: serde_json::Value = serde_json::from_str(request).unwrap();
+                request["variables"]["requests"].as_array().unwrap().len()
+            })
+            .collect();
+        assert_eq!(sent, vec![10, 10, 5]);
+    }
+
+    #[tokio::test]
+    async fn missing_status_endpoint_is_unsupported() {
+        let server = StubServer::start(404, "");
+        let client = POIClient::new().unwrap();
+        let error = client.fetch_poi(&server.url, "Qm", 1).await.unwrap_err();
+        assert!(matches!(error, PoiError::UnsupportedEndpoint(_)));
+    }
+
+    #[tokio::test]
+    async fn chain_head_is_read_for_the_requested_network() {
+        let server = StubServer::start(
+            200,
+            r#"{"data":{"indexingStatuses":[{"chains":[
+                {"network":"arbitrum-one","chainHeadBlock":{"number":"300"}},
+                {"network":"mainnet","chainHeadBlock":{"number":"200"}}
+            ]}]}}"#,
+        );
+        let client = POIClient::new().unwrap();
+        let head = client
+            .fetch_chain_head(&server.url, "Qm", "mainnet")
+            .await
+            .unwrap();
+        assert_eq!(head, 200);
+        assert!(client
+            .fetch_chain_head(&server.url, "Qm", "base")
+            .await
+            .is_err());
     }
 }
@@ -1,31 +1,287 @@
 use anyhow::{anyhow, Result};
-use graph_networks_registry::NetworksRegistry;
+use etcetera::{choose_base_strategy, BaseStrategy};
+use graph_networks_registry::{Network, NetworksRegistry};
+use reqwest::Client;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How long a downloaded registry is reused before it is fetched again
+const CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// A copy of the registry shipped with poipal, for when it can neither be
+/// downloaded nor read from the cache. Refreshed with
+/// `scripts/update-networks-registry.sh`.
+const SNAPSHOT: &str = include_str!("networks_registry_snapshot.json");
+
+fn is_stale(age: Duration) -> bool {
+    age > CACHE_TTL
+}
+
+/// Where the loaded registry came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistrySource {
+    File(PathBuf),
+    Cache { stale: bool },
+    Download,
+    Snapshot,
+}
+
+impl fmt::Display for RegistrySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File(path) => write!(f, "file {}", path.display()),
+            Self::Cache { stale: false } => write!(f, "disk cache"),
+            Self::Cache { stale: true } => write!(f, "stale disk cache"),
+            Self::Download => write!(f, "networks-registry.thegraph.com"),
+            Self::Snapshot => write!(f, "bundled snapshot"),
+        }
+    }
+}
 
 pub struct RegistryClient {
     registry: NetworksRegistry,
+    source: RegistrySource,
 }
 
 impl RegistryClient {
+    /// Loads the registry from the disk cache while it is fresh, otherwise
+    /// downloads the latest version and caches it. When the download fails,
+    /// a stale cache and then the bundled snapshot are used instead.
     pub async fn new() -> Result<Self> {
-        let registry = NetworksRegistry::from_latest_version().await?;
-        Ok(Self { registry })
+        let cache_path = Self::cache_path();
+        let cached = cache_path.as_deref().and_then(|path| {
+            let age = path.metadata().ok()?.modified().ok()?.elapsed().ok()?;
+            let registry = NetworksRegistry::from_file(path).ok()?;
+            Some((registry, age))
+        });
+
+        let downloaded = match &cached {
+            Some((_, age)) if !is_stale(*age) => None,
+            _ => Self::download(&NetworksRegistry::get_latest_version_url())
+                .await
+                .ok(),
+        };
+
+        // Caching is best-effort; a read-only home still works
+        if let (Some((_, json)), Some(path)) = (&downloaded, &cache_path) {
+            if let Some(dir) = path.parent() {
+                let _ = std::fs::create_dir_all(dir);
+            }
+            let _ = std::fs::write(path, json);
+        }
+
+        Self::select(cached, downloaded.map(|(registry, _)| registry))
+    }
+
+    /// Picks the registry to use: a fresh cache, then a successful
+    /// download, then a stale cache, then the bundled snapshot.
+    fn select(
+        cached: Option<(NetworksRegistry, Duration)>,
+        downloaded: Option<NetworksRegistry>,
+    ) -> Result<Self> {
+        match (cached, downloaded) {
+            (Some((registry, age)), _) if !is_stale(age) => Ok(Self::with_source(
+                registry,
+                RegistrySource::Cache { stale: false },
+            )),
+            (_, Some(registry)) => Ok(Self::with_source(registry, RegistrySource::Download)),
+            (Some((registry, _)), None) => Ok(Self::with_source(
+                registry,
+                RegistrySource::Cache { stale: true },
+            )),
+            (None, None) => Self::snapshot(),
+        }
+    }
+
+    /// Loads the registry from a local JSON file, e.g. a pinned version.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let registry = NetworksRegistry::from_file(path).map_err(|e| {
+            anyhow!(
+                "Failed to load networks registry from {}: {}",
+                path.display(),
+                e
+            )
+        })?;
+        Ok(Self::with_source(
+            registry,
+            RegistrySource::File(path.to_path_buf()),
+        ))
+    }
+
+    pub fn snapshot() -> Result<Self> {
+        let registry = NetworksRegistry::from_json(SNAPSHOT)?;
+        Ok(Self::with_source(registry, RegistrySource::Snapshot))
+    }
+
+    fn with_source(registry: NetworksRegistry, source: RegistrySource) -> Self {
+        Self { registry, source }
+    }
+
+    fn cache_path() -> Option<PathBuf> {
+        let strategy = choose_base_strategy().ok()?;
+        Some(
+            strategy
+                .cache_dir()
+                .join("poipal")
+                .join("networks-registry.json"),
+        )
+    }
+
+    /// Downloads and parses the registry, returning it with the raw JSON to
+    /// cache. A body that is not a registry (e.g. a captive portal page)
+    /// counts as a failed download.
+    async fn download(url: &str) -> Result<(NetworksRegistry, String)> {
+        let client = Client::builder().timeout(Duration::from_secs(10)).build()?;
+        let response = client.get(url).send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(anyhow!("Registry download failed: HTTP {}", status));
+        }
+        let json = response.text().await?;
+        let registry = NetworksRegistry::from_json(&json)
+            .map_err(|e| anyhow!("Downloaded networks registry is invalid: {}", e))?;
+        Ok((registry, json))
+    }
+
+    pub fn source(&self) -> &RegistrySource {
+        &self.source
     }
 
-    pub async fn get_public_rpc_url(&self, network: &str) -> Result<String> {
+    pub fn version(&self) -> &str {
+        &self.registry.version
+    }
+
+    /// Networks whose ID, alias, name or CAIP-2 ID contains `query`
+    /// (case-insensitive), or every network when `query` is `None`.
+    pub fn search(&self, query: Option<&str>) -> Vec<&Network> {
+        let query = query.map(str::to_lowercase);
+        self.registry
+            .networks
+            .iter()
+            .filter(|network| {
+                let Some(query) = &query else {
+                    return true;
+                };
+                std::iter::once(&network.id)
+                    .chain(network.aliases.iter().flatten())
+                    .chain([&network.full_name, &network.caip2_id])
+                    .any(|field| field.to_lowercase().contains(query.as_str()))
+            })
+            .collect()
+    }
+
+    /// CAIP-2 chain ID of `network`, e.g. `eip155:1` for `mainnet`.
+    pub fn get_caip2_id(&self, network: &str) -> Result<String> {
+        self.registry
+            .get_network_by_graph_id(network)
+            .map(|network_info| network_info.caip2_id.clone())
+            .ok_or_else(|| anyhow!("Network '{}' not found in registry", network))
+    }
+
+    /// EVM chain ID of `network`, when its CAIP-2 ID is an `eip155` one.
+    pub fn get_chain_id(&self, network: &str) -> Option<u64> {
+        self.get_caip2_id(network)
+            .ok()?
+            .strip_prefix("eip155:")?
+            .parse()
+            .ok()
+    }
+
+    /// Every public RPC URL listed for `network`, in registry order. URLs
+    /// that need an API key substituted in are skipped.
+    pub fn get_rpc_urls(&self, network: &str) -> Result<Vec<String>> {
         let network_info = self
             .registry
             .get_network_by_graph_id(network)
             .ok_or_else(|| anyhow!("Network '{}' not found in registry", network))?;
 
-        let rpc_urls = network_info
+        let rpc_urls: Vec<String> = network_info
             .rpc_urls
-            .as_ref()
-            .ok_or_else(|| anyhow!("No RPC URLs available for network '{}'", network))?;
+            .iter()
+            .flatten()
+            .filter(|url| !url.contains('{'))
+            .cloned()
+            .collect();
 
         if rpc_urls.is_empty() {
-            return Err(anyhow!("RPC URLs list is empty for network '{}'", network));
+            return Err(anyhow!("No RPC URLs available for network '{}'", network));
         }
 
-        Ok(rpc_urls[0].clone())
+        Ok(rpc_urls)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::stub::StubServer;
+
+    const HOUR: Duration = Duration::from_secs(60 * 60);
+
+    fn registry(version: &str) -> NetworksRegistry {
+        let mut registry = NetworksRegistry::from_json(SNAPSHOT).unwrap();
+        registry.version = version.to_string();
+        registry
+    }
+
+    fn select(
+        cached: Option<(&str, Duration)>,
+        downloaded: Option<&str>,
+    ) -> (RegistrySource, String) {
+        let client = RegistryClient::select(
+            cached.map(|(version, age)| (registry(version), age)),
+            downloaded.map(registry),
+        )
+        .unwrap();
+        (client.source, client.registry.version)
+    }
+
+    #[test]
+    fn fresh_cache_wins_over_download() {
+        let (source, version) = select(Some(("cached", HOUR)), Some("downloaded"));
+        assert_eq!(source, RegistrySource::Cache { stale: false });
+        assert_eq!(version, "cached");
+    }
+
+    #[test]
+    fn stale_cache_is_replaced_by_download() {
+        let (source, version) = select(Some(("cached", 25 * HOUR)), Some("downloaded"));
+        assert_eq!(source, RegistrySource::Download);
+        assert_eq!(version, "downloaded");
+    }
+
+    #[test]
+    fn stale_cache_is_used_when_download_fails() {
+        let (source, version) = select(Some(("cached", 25 * HOUR)), None);
+        assert_eq!(source, RegistrySource::Cache { stale: true });
+        assert_eq!(version, "cached");
+    }
+
+    #[test]
+    fn snapshot_is_used_without_cache_or_download() {
+        let (source, _) = select(None, None);
+        assert_eq!(source, RegistrySource::Snapshot);
+    }
+
+    #[test]
+    fn cache_expires_after_ttl() {
+        assert!(!is_stale(CACHE_TTL));
+        assert!(is_stale(CACHE_TTL + Duration::from_secs(1)));
+    }
+
+    #[tokio::test]
+    async fn non_registry_body_is_a_failed_download() {
+        let server = StubServer::start(200, "<html>Sign in to the Wi-Fi</html>");
+        assert!(RegistryClient::download(&server.url).await.is_err());
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn registry_body_is_downloaded() {
+        let server = StubServer::start(200, SNAPSHOT);
+        let (registry, json) = RegistryClient::download(&server.url).await.unwrap();
+        assert_eq!(json, SNAPSHOT);
+        assert!(!registry.networks.is_empty());
     }
 }
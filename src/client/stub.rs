@@ -0,0 +1,85 @@
+//! A minimal HTTP server for tests that answers requests with canned or
+//! computed responses and keeps the request bodies it received.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+pub struct StubServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl StubServer {
+    /// Answers every request with `body`.
+    pub fn start(status: u16, body: &'static str) -> Self {
+        Self::with_responses(vec![(status, body)])
+    }
+
+    /// Answers the n-th request with the n-th response, repeating the last
+    /// one once the sequence is exhausted.
+    pub fn with_responses(responses: Vec<(u16, &'static str)>) -> Self {
+        assert!(!responses.is_empty(), "stub needs at least one response");
+        let count = Mutex::new(0);
+        Self::with_handler(move |_, _| {
+            let mut count = count.lock().unwrap();
+            let (status, body) = responses[(*count).min(responses.len() - 1)];
+            *count += 1;
+            (status, body)
+        })
+    }
+
+    /// Answers every request with what `handler` returns for its path and body.
+    pub fn with_handler<B: Into<Vec<u8>>>(
+        handler: impl Fn(&str, &str) -> (u16, B) + Send + 'static,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let received = Arc::clone(&requests);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+                let mut reader = BufReader::new(&stream);
+                let mut request_line = String::new();
+                let _ = reader.read_line(&mut request_line);
+                let path = request_line.split(' ').nth(1).unwrap_or("/").to_string();
+                let mut content_length = 0;
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap_or(0) > 0 && line != "\r\n" {
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap_or(0);
+                        }
+                    }
+                    line.clear();
+                }
+                let mut request = vec![0; content_length];
+                let _ = reader.read_exact(&mut request);
+                let request = String::from_utf8_lossy(&request).into_owned();
+                let (status, body) = handler(&path, &request);
+                let body: Vec<u8> = body.into();
+                received.lock().unwrap().push(request);
+
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {} Stub\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    body.len()
+                );
+                let _ = stream.write_all(&body);
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    /// Bodies of the requests received so far, in arrival order.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests.lock().unwrap().clone()
+    }
+}
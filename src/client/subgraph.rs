@@ -1,64 +1,695 @@
-use anyhow::Result;
-use reqwest::Client;
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use reqwest::{Client, Url};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::json;
 use std::collections::BTreeMap;
-use std::time::Duration;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::OnceCell;
 
-use crate::models::{AllocationsData, GraphQLQuery, GraphQLResponse, Indexer};
+use crate::client::{post_graphql, QueryRecorder};
+use crate::models::{Allocation, AllocationsData, DeploymentId, GraphQLQuery, Indexer, SubgraphId};
+
+pub(crate) const GATEWAY_SUBGRAPHS_URL: &str = "https://gateway.thegraph.com/api/subgraphs/id";
+
+/// Whether `url` points at The Graph's gateway, the only endpoint the API key
+/// is sent to.
+pub fn is_gateway_url(url: &str) -> bool {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| {
+            url.host_str()
+                .map(|host| host == "thegraph.com" || host.ends_with(".thegraph.com"))
+        })
+        .unwrap_or(false)
+}
+
+/// Maximum page size accepted by graph-node
+const PAGE_SIZE: usize = 1000;
+
+const SCHEMA_QUERY: &str = r#"
+    query Schema {
+        __type(name: "Provision") {
+            name
+        }
+    }
+"#;
+
+const CURRENT_EPOCH_QUERY: &str = r#"
+    query CurrentEpoch {
+        graphNetwork(id: "1") {
+            currentEpoch
+        }
+    }
+"#;
+
+const SUBGRAPH_VERSIONS_QUERY: &str = r#"
+    query SubgraphVersions($id: ID!) {
+        subgraph(id: $id) {
+            currentVersion {
+                version
+                subgraphDeployment {
+                    ipfsHash
+                }
+            }
+            versions(first: 1000, orderBy: version, orderDirection: asc) {
+                version
+                subgraphDeployment {
+                    ipfsHash
+                }
+            }
+        }
+    }
+"#;
+
+const LEGACY_ALLOCATIONS_QUERY: &str = r#"
+    query Allocations($where: Allocation_filter!, $first: Int!) {
+        allocations(first: $first, orderBy: id, orderDirection: asc, where: $where) {
+            id
+            status
+            poi
+            closedAtEpoch
+            indexer {
+                id
+                url
+                stakedTokens
+            }
+        }
+    }
+"#;
+
+const HORIZON_ALLOCATIONS_QUERY: &str = r#"
+    query Allocations($where: Allocation_filter!, $first: Int!) {
+        allocations(first: $first, orderBy: id, orderDirection: asc, where: $where) {
+            id
+            status
+            poi
+            closedAtEpoch
+            indexer {
+                id
+                url
+                stakedTokens
+            }
+            provision {
+                url
+            }
+        }
+    }
+"#;
+
+/// Network subgraph schema generations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaVersion {
+    /// Pre-Horizon schema: indexer URLs come from the service registry
+    Legacy,
+    /// Graph Horizon schema: allocations belong to SubgraphService provisions
+    Horizon,
+}
+
+#[derive(Debug, Deserialize)]
+struct SchemaData {
+    #[serde(rename = "__type")]
+    provision_type: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CurrentEpochData {
+    #[serde(rename = "graphNetwork")]
+    graph_network: Option<GraphNetworkData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphNetworkData {
+    #[serde(rename = "currentEpoch")]
+    current_epoch: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubgraphVersionsData {
+    subgraph: Option<SubgraphData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubgraphData {
+    #[serde(rename = "currentVersion")]
+    current_version: Option<SubgraphVersionData>,
+    versions: Vec<SubgraphVersionData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubgraphVersionData {
+    version: u32,
+    #[serde(rename = "subgraphDeployment")]
+    subgraph_deployment: SubgraphDeploymentData,
+}
+
+#[derive(Debug, Deserialize)]
+struct SubgraphDeploymentData {
+    #[serde(rename = "ipfsHash")]
+    ipfs_hash: String,
+}
+
+/// A published version of a subgraph and the deployment it points to.
+#[derive(Debug, Clone)]
+pub struct SubgraphVersion {
+    pub version: u32,
+    pub current: bool,
+    pub deployment: DeploymentId,
+}
+
+/// How far back closed allocations are considered when discovering indexers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClosedSince {
+    Epochs(u64),
+    Duration(Duration),
+}
+
+impl FromStr for ClosedSince {
+    type Err = String;
+
+    /// Accepts a number of epochs (`3`) or a duration (`90m`, `12h`, `7d`).
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        if let Ok(epochs) = value.parse::<u64>() {
+            return Ok(ClosedSince::Epochs(epochs));
+        }
+
+        let split = value
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(value.len());
+        let (amount, unit) = value.split_at(split);
+        let amount: u64 = amount
+            .parse()
+            .map_err(|_| format!("Invalid value '{}': expected epochs or a duration", value))?;
+        let seconds = match unit {
+            "s" => 1,
+            "m" => 60,
+            "h" => 60 * 60,
+            "d" => 24 * 60 * 60,
+            _ => {
+                return Err(format!(
+                    "Invalid duration unit '{}': expected s, m, h or d",
+                    unit
+                ))
+            }
+        };
+        let seconds = amount
+            .checked_mul(seconds)
+            .ok_or_else(|| format!("Invalid duration '{}': too large", value))?;
+        Ok(ClosedSince::Duration(Duration::from_secs(seconds)))
+    }
+}
+
+/// Graph protocol deployments whose network subgraph is served by the gateway.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GraphNetwork {
+    ArbitrumOne,
+    ArbitrumSepolia,
+}
+
+impl GraphNetwork {
+    /// Subgraph ID of the network subgraph for this protocol deployment.
+    pub fn network_subgraph_id(&self) -> &'static str {
+        match self {
+            GraphNetwork::ArbitrumOne => "DZz4kDTdmzWLWsV373w2bSmoar3umKKH9y82SUKr5qmp",
+            GraphNetwork::ArbitrumSepolia => "3xQHhMudr1oh69ut36G2mbzpYmYxwqCeU6wwqyCDCnqV",
+        }
+    }
+
+    /// Subgraph ID of the Epoch Block Oracle used by this protocol deployment,
+    /// when known.
+    pub fn epoch_oracle_subgraph_id(&self) -> Option<&'static str> {
+        match self {
+            GraphNetwork::ArbitrumOne => Some("4KFYqUWRTZQ9gn1GfHrn5mhE8QQHmJfhT2cBfsmJhGzU"),
+            GraphNetwork::ArbitrumSepolia => None,
+        }
+    }
+
+    pub fn gateway_url(&self) -> String {
+        format!("{}/{}", GATEWAY_SUBGRAPHS_URL, self.network_subgraph_id())
+    }
+}
 
 pub struct GraphClient {
     client: Client,
     network_url: String,
-    api_key: String,
+    api_key: Option<String>,
+    schema: OnceCell<SchemaVersion>,
+    recorder: Option<QueryRecorder>,
 }
 
 impl GraphClient {
-    pub fn new(api_key: String) -> Result<Self> {
+    /// Creates a client for any network subgraph endpoint. The API key is
+    /// sent as a bearer token when present; self-hosted graph-nodes need none.
+    /// See [`is_gateway_url`] before passing a key for a user-supplied URL.
+    pub fn new(network_url: String, api_key: Option<String>) -> Result<Self> {
         let client = Client::builder().timeout(Duration::from_secs(30)).build()?;
 
-        let network_url = "https://gateway.thegraph.com/api/subgraphs/id/DZz4kDTdmzWLWsV373w2bSmoar3umKKH9y82SUKr5qmp".to_string();
-
         Ok(Self {
             client,
             network_url,
             api_key,
+            schema: OnceCell::new(),
+            recorder: None,
         })
     }
 
-    pub async fn fetch_indexers(&self, deployment: &str) -> Result<BTreeMap<String, Indexer>> {
-        let query = format!(
-            r#"{{
-                allocations( 
-                    where: {{
-                        status: Active
-                        subgraphDeployment_: {{ ipfsHash: "{}" }}
-                    }}
-                ) {{
-                    indexer {{
-                        id
-                        url
-                    }}
-                }}
-            }}"#,
-            deployment
-        );
+    /// Creates a client for the network subgraph of `network` through the gateway.
+    pub fn for_network(network: GraphNetwork, api_key: String) -> Result<Self> {
+        Self::new(network.gateway_url(), Some(api_key))
+    }
+
+    /// Records every query sent by this client in `recorder`.
+    pub fn with_recorder(mut self, recorder: QueryRecorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
 
-        let mut request = self
-            .client
-            .post(&self.network_url)
-            .json(&GraphQLQuery { query });
+    /// Detects whether the network subgraph exposes the Horizon schema. The
+    /// result is cached for the lifetime of the client.
+    pub async fn schema_version(&self) -> Result<SchemaVersion> {
+        self.schema
+            .get_or_try_init(|| async {
+                let query = GraphQLQuery {
+                    query: SCHEMA_QUERY,
+                    variables: json!({}),
+                };
+                let data: SchemaData = self.query(query).await?;
+                Ok(match data.provision_type {
+                    Some(_) => SchemaVersion::Horizon,
+                    None => SchemaVersion::Legacy,
+                })
+            })
+            .await
+            .copied()
+    }
+
+    pub async fn current_epoch(&self) -> Result<u64> {
+        let query = GraphQLQuery {
+            query: CURRENT_EPOCH_QUERY,
+            variables: json!({}),
+        };
+        let data: CurrentEpochData = self.query(query).await?;
+        data.graph_network
+            .map(|network| network.current_epoch)
+            .ok_or_else(|| anyhow!("Network subgraph has no graphNetwork entity"))
+    }
 
-        request = request.bearer_auth(&self.api_key);
+    /// Resolves `subgraph` to the deployment of `version`, or of its current
+    /// version when `version` is not given.
+    pub async fn resolve_subgraph(
+        &self,
+        subgraph: &SubgraphId,
+        version: Option<u32>,
+    ) -> Result<SubgraphVersion> {
+        let query = GraphQLQuery {
+            query: SUBGRAPH_VERSIONS_QUERY,
+            variables: json!({ "id": subgraph.to_string() }),
+        };
+        let data: SubgraphVersionsData = self.query(query).await?;
+        let subgraph_data = data
+            .subgraph
+            .ok_or_else(|| anyhow!("Subgraph {} not found in the network subgraph", subgraph))?;
+        let current = subgraph_data
+            .current_version
+            .as_ref()
+            .map(|current| current.version);
 
-        let response = request.send().await?;
-        let data: GraphQLResponse<AllocationsData> = response.json().await?;
+        let resolved = match version {
+            Some(version) => subgraph_data
+                .versions
+                .into_iter()
+                .find(|v| v.version == version)
+                .ok_or_else(|| anyhow!("Subgraph {} has no version {}", subgraph, version))?,
+            None => subgraph_data
+                .current_version
+                .ok_or_else(|| anyhow!("Subgraph {} has no current version", subgraph))?,
+        };
 
-        let indexers = data
-            .data
-            .allocations
-            .into_iter()
-            .map(|allocation| (allocation.indexer.id.clone(), allocation.indexer))
-            .collect();
+        Ok(SubgraphVersion {
+            version: resolved.version,
+            current: current == Some(resolved.version),
+            deployment: resolved.subgraph_deployment.ipfs_hash.parse()?,
+        })
+    }
+
+    /// Fetches every indexer with an active allocation on `deployment`, plus
+    /// indexers whose allocation closed within `closed_since` when given.
+    /// Indexers without a registered service URL are skipped, since their
+    /// POIs cannot be queried.
+    pub async fn fetch_indexers(
+        &self,
+        deployment: &DeploymentId,
+        closed_since: Option<ClosedSince>,
+    ) -> Result<BTreeMap<String, Indexer>> {
+        let mut filters = vec![json!({
+            "status": "Active",
+            "subgraphDeployment_": { "ipfsHash": deployment.ipfs_hash() },
+        })];
+
+        if closed_since.is_some() {
+            filters.push(
+                self.closed_allocations_filter(deployment, closed_since)
+                    .await?,
+            );
+        }
+
+        // Active allocations come first, so an indexer that is still
+        // allocated keeps its active status
+        let mut indexers: BTreeMap<String, Indexer> = BTreeMap::new();
+        for filter in filters {
+            for allocation in self.fetch_allocations(filter).await? {
+                let Some(indexer) = allocation.into_indexer() else {
+                    continue;
+                };
+                match indexers.get_mut(&indexer.id) {
+                    Some(existing) => existing.allocation_ids.extend(indexer.allocation_ids),
+                    None => {
+                        indexers.insert(indexer.id.clone(), indexer);
+                    }
+                }
+            }
+        }
 
         Ok(indexers)
     }
+
+    /// Fetches the closed allocations on `deployment`, optionally limited to
+    /// those closed within `closed_since`, including the POI each indexer
+    /// submitted on chain.
+    pub async fn fetch_closed_allocations(
+        &self,
+        deployment: &DeploymentId,
+        closed_since: Option<ClosedSince>,
+    ) -> Result<Vec<Allocation>> {
+        let filter = self
+            .closed_allocations_filter(deployment, closed_since)
+            .await?;
+        self.fetch_allocations(filter).await
+    }
+
+    async fn closed_allocations_filter(
+        &self,
+        deployment: &DeploymentId,
+        closed_since: Option<ClosedSince>,
+    ) -> Result<serde_json::Value> {
+        let mut filter = json!({
+            "status_not": "Active",
+            "subgraphDeployment_": { "ipfsHash": deployment.ipfs_hash() },
+        });
+        match closed_since {
+            Some(ClosedSince::Epochs(epochs)) => {
+                let since = self.current_epoch().await?.saturating_sub(epochs);
+                filter["closedAtEpoch_gte"] = json!(since);
+            }
+            Some(ClosedSince::Duration(duration)) => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH)?;
+                filter["closedAt_gte"] = json!(now.saturating_sub(duration).as_secs());
+            }
+            None => {}
+        }
+        Ok(filter)
+    }
+
+    /// Fetches all allocations matching `filter`, paging by ID.
+    async fn fetch_allocations(&self, filter: serde_json::Value) -> Result<Vec<Allocation>> {
+        let allocations_query = match self.schema_version().await? {
+            SchemaVersion::Legacy => LEGACY_ALLOCATIONS_QUERY,
+            SchemaVersion::Horizon => HORIZON_ALLOCATIONS_QUERY,
+        };
+
+        let mut allocations = Vec::new();
+        let mut last_id = String::new();
+
+        loop {
+            let mut filter = filter.clone();
+            filter["id_gt"] = json!(last_id);
+
+            let query = GraphQLQuery {
+                query: allocations_query,
+                variables: json!({ "where": filter, "first": PAGE_SIZE }),
+            };
+
+            let data: AllocationsData = self.query(query).await?;
+            let page_len = data.allocations.len();
+            if let Some(last) = data.allocations.last() {
+                last_id = last.id.clone();
+            }
+            allocations.extend(data.allocations);
+
+            if page_len < PAGE_SIZE {
+                break;
+            }
+        }
+
+        Ok(allocations)
+    }
+
+    async fn query<T: DeserializeOwned>(&self, query: GraphQLQuery) -> Result<T> {
+        post_graphql(
+            &self.client,
+            &self.network_url,
+            self.api_key.as_deref(),
+            &query,
+            "Network subgraph",
+            self.recorder.as_ref(),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::stub::StubServer;
+
+    const DEPLOYMENT: &str = "QmaS7sZNgzqij8FKb7VPK4kppqqsgzujogKDTjTkAcn59j";
+    const LEGACY_SCHEMA: &str = r#"{"data":{"__type":null}}"#;
+
+    fn deployment() -> DeploymentId {
+        DEPLOYMENT.parse().unwrap()
+    }
+
+    fn client(server: &StubServer) -> GraphClient {
+        GraphClient::new(server.url.clone(), None).unwrap()
+    }
+
+    fn variables(request: &str) -> serde_json::Value {
+        serde_json::from_str::<serde_json::Value>(request).unwrap()["variables"].clone()
+    }
+
+    /// An allocations response page, leaked to fit the stub's static bodies.
+    fn allocations_page(allocations: Vec<serde_json::Value>) -> &'static str {
+        let page = json!({ "data": { "allocations": allocations } });
+        Box::leak(page.to_string().into_boxed_str())
+    }
+
+    fn allocation(id: usize, indexer: usize) -> serde_json::Value {
+        json!({
+            "id": format!("0x{:040x}", id),
+            "status": "Active",
+            "poi": null,
+            "closedAtEpoch": null,
+            "indexer": {
+                "id": format!("0xindexer{}", indexer),
+                "url": format!("https://indexer{}.example.com/", indexer),
+                "stakedTokens": "1000",
+            },
+        })
+    }
+
+    #[test]
+    fn only_the_graph_hosts_are_gateways() {
+        assert!(is_gateway_url(GATEWAY_SUBGRAPHS_URL));
+        assert!(is_gateway_url(
+            "https://gateway-arbitrum.network.thegraph.com/api/subgraphs/id/x"
+        ));
+        assert!(!is_gateway_url(
+            "http://localhost:8000/subgraphs/name/network"
+        ));
+        assert!(!is_gateway_url("https://thegraph.com.example.org/api"));
+        assert!(!is_gateway_url("not a url"));
+    }
+
+    #[test]
+    fn parses_closed_since_durations() {
+        let parse = |value: &str| value.parse::<ClosedSince>().unwrap();
+        assert_eq!(parse("45s"), ClosedSince::Duration(Duration::from_secs(45)));
+        assert_eq!(
+            parse("90m"),
+            ClosedSince::Duration(Duration::from_secs(90 * 60))
+        );
+        assert_eq!(
+            parse("12h"),
+            ClosedSince::Duration(Duration::from_secs(12 * 3600))
+        );
+        assert_eq!(
+            parse("7d"),
+            ClosedSince::Duration(Duration::from_secs(7 * 86400))
+        );
+    }
+
+    #[test]
+    fn parses_closed_since_epochs() {
+        assert_eq!("3".parse::<ClosedSince>(), Ok(ClosedSince::Epochs(3)));
+        assert_eq!(" 0 ".parse::<ClosedSince>(), Ok(ClosedSince::Epochs(0)));
+    }
+
+    #[test]
+    fn rejects_bad_closed_since_values() {
+        for value in ["", "d", "7w", "7 d", "-3", "1.5h", "h7"] {
+            assert!(value.parse::<ClosedSince>().is_err(), "{}", value);
+        }
+        let too_large = format!("{}d", u64::MAX / 60);
+        assert!(too_large
+            .parse::<ClosedSince>()
+            .unwrap_err()
+            .contains("too large"));
+    }
+
+    #[tokio::test]
+    async fn pages_allocations_by_id() {
+        // A full page, then a partial one; 600 indexers allocated twice
+        let first: Vec<_> = (0..PAGE_SIZE).map(|i| allocation(i, i % 600)).collect();
+        let second: Vec<_> = (PAGE_SIZE..PAGE_SIZE + 200)
+            .map(|i| allocation(i, i % 600))
+            .collect();
+        let server = StubServer::with_responses(vec![
+            (200, LEGACY_SCHEMA),
+            (200, allocations_page(first)),
+            (200, allocations_page(second)),
+        ]);
+
+        let indexers = client(&server)
+            .fetch_indexers(&deployment(), None)
+            .await
+            .unwrap();
+        assert_eq!(indexers.len(), 600);
+        assert_eq!(indexers["0xindexer0"].allocation_ids.len(), 2);
+        assert_eq!(indexers["0xindexer599"].allocation_ids.len(), 2);
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        let first_page = variables(&requests[1]);
+        assert_eq!(first_page["first"], PAGE_SIZE);
+        assert_eq!(first_page["where"]["id_gt"], "");
+        assert_eq!(first_page["where"]["status"], "Active");
+        assert_eq!(
+            variables(&requests[2])["where"]["id_gt"],
+            format!("0x{:040x}", PAGE_SIZE - 1)
+        );
+    }
+
+    #[tokio::test]
+    async fn maps_horizon_provisions_to_indexer_urls() {
+        let mut provisioned = allocation(1, 1);
+        provisioned["indexer"]["url"] = json!(null);
+        provisioned["provision"] = json!({ "url": "https://horizon.example.com/" });
+        let mut unregistered = allocation(2, 2);
+        unregistered["indexer"]["url"] = json!(null);
+        unregistered["provision"] = json!({ "url": null });
+        let server = StubServer::with_responses(vec![
+            (200, r#"{"data":{"__type":{"name":"Provision"}}}"#),
+            (200, allocations_page(vec![provisioned, unregistered])),
+        ]);
+        let client = client(&server);
+
+        assert_eq!(
+            client.schema_version().await.unwrap(),
+            SchemaVersion::Horizon
+        );
+        let indexers = client.fetch_indexers(&deployment(), None).await.unwrap();
+        assert_eq!(indexers.len(), 1);
+        assert_eq!(indexers["0xindexer1"].url, "https://horizon.example.com/");
+
+        // The schema is detected once, and only Horizon queries provisions
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].contains("provision"));
+    }
+
+    #[tokio::test]
+    async fn uses_service_urls_on_the_legacy_schema() {
+        let server = StubServer::with_responses(vec![
+            (200, LEGACY_SCHEMA),
+            (200, allocations_page(vec![allocation(1, 1)])),
+        ]);
+        let client = client(&server);
+
+        assert_eq!(
+            client.schema_version().await.unwrap(),
+            SchemaVersion::Legacy
+        );
+        let indexers = client.fetch_indexers(&deployment(), None).await.unwrap();
+        assert_eq!(indexers["0xindexer1"].url, "https://indexer1.example.com/");
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(!requests[1].contains("provision"));
+    }
+
+    const SUBGRAPH_VERSIONS: &str = r#"{"data":{"subgraph":{
+        "currentVersion":{"version":2,"subgraphDeployment":{"ipfsHash":"QmaS7sZNgzqij8FKb7VPK4kppqqsgzujogKDTjTkAcn59j"}},
+        "versions":[
+            {"version":1,"subgraphDeployment":{"ipfsHash":"Qmf412jQZiuVUtdgnB36FXFX7xg5V6KEbSJ4dpQuhkLyfD"}},
+            {"version":2,"subgraphDeployment":{"ipfsHash":"QmaS7sZNgzqij8FKb7VPK4kppqqsgzujogKDTjTkAcn59j"}},
+            {"version":3,"subgraphDeployment":{"ipfsHash":"QmUNLLsPACCz1vLxQVkXqqLX5R1X345qqfHbsf67hvA3Nn"}}
+        ]
+    }}}"#;
+
+    fn subgraph_id() -> SubgraphId {
+        "DZz4kDTdmzWLWsV373w2bSmoar3umKKH9y82SUKr5qmp"
+            .parse()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn resolves_subgraphs_to_their_current_version() {
+        let server = StubServer::start(200, SUBGRAPH_VERSIONS);
+        let resolved = client(&server)
+            .resolve_subgraph(&subgraph_id(), None)
+            .await
+            .unwrap();
+        assert_eq!(resolved.version, 2);
+        assert!(resolved.current);
+        assert_eq!(resolved.deployment, deployment());
+        assert_eq!(
+            variables(&server.requests()[0]),
+            json!({ "id": "DZz4kDTdmzWLWsV373w2bSmoar3umKKH9y82SUKr5qmp" })
+        );
+    }
+
+    #[tokio::test]
+    async fn resolves_other_subgraph_versions() {
+        let server = StubServer::start(200, SUBGRAPH_VERSIONS);
+        let client = client(&server);
+
+        let resolved = client
+            .resolve_subgraph(&subgraph_id(), Some(3))
+            .await
+            .unwrap();
+        assert_eq!(resolved.version, 3);
+        assert!(!resolved.current);
+        assert_eq!(
+            resolved.deployment.ipfs_hash(),
+            "QmUNLLsPACCz1vLxQVkXqqLX5R1X345qqfHbsf67hvA3Nn"
+        );
+
+        let error = client
+            .resolve_subgraph(&subgraph_id(), Some(4))
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("has no version 4"));
+    }
+
+    #[tokio::test]
+    async fn unknown_subgraphs_are_an_error() {
+        let server = StubServer::start(200, r#"{"data":{"subgraph":null}}"#);
+        let error = client(&server)
+            .resolve_subgraph(&subgraph_id(), None)
+            .await
+            .unwrap_err();
+        assert!(error.to_string().contains("not found"));
+    }
 }
@@ -0,0 +1,366 @@
+use anyhow::{anyhow, Result};
+use clap::Args;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::client::chain_head::{fetch_chain_head, ChainHeadProvider};
+use crate::client::epoch::{EpochOracleClient, EpochSelector};
+use crate::client::eth::EthClient;
+use crate::client::ipfs::{IpfsClient, IpfsEndpoint};
+use crate::client::registry::{RegistryClient, RegistrySource};
+use crate::client::subgraph::{is_gateway_url, ClosedSince, GraphClient, GraphNetwork};
+use crate::models::{DeploymentId, DeploymentRef, Indexer, SubgraphId};
+use crate::utils::{display_deployment, display_info, display_warning};
+
+/// The deployment a command runs against, given directly or as a subgraph
+/// resolved through the network subgraph.
+#[derive(Debug, Clone, Args)]
+pub struct DeploymentArgs {
+    #[arg(
+        help = "Deployment ID (IPFS hash or 0x-prefixed bytes32), or a subgraph ID",
+        required_unless_present = "subgraph",
+        conflicts_with = "subgraph"
+    )]
+    pub deployment: Option<DeploymentRef>,
+
+    #[arg(
+        long,
+        help = "Subgraph ID (as shown on Explorer) to resolve to a deployment"
+    )]
+    pub subgraph: Option<SubgraphId>,
+
+    #[arg(
+        long,
+        help = "Subgraph version to resolve (defaults to the current version)"
+    )]
+    pub version: Option<u32>,
+}
+
+impl From<DeploymentId> for DeploymentArgs {
+    fn from(deployment: DeploymentId) -> Self {
+        Self {
+            deployment: Some(DeploymentRef::Deployment(deployment)),
+            subgraph: None,
+            version: None,
+        }
+    }
+}
+
+impl DeploymentArgs {
+    /// Returns the deployment to run against, looking subgraphs up in the
+    /// network subgraph, and displays what was resolved.
+    pub async fn resolve(&self, network_subgraph: &NetworkSubgraphArgs) -> Result<DeploymentId> {
+        let subgraph = match (&self.deployment, &self.subgraph) {
+            (Some(DeploymentRef::Deployment(deployment)), _) => {
+                if self.version.is_some() {
+                    return Err(anyhow!("--version only applies to subgraph IDs"));
+                }
+                display_deployment(deployment);
+                return Ok(deployment.clone());
+            }
+            (Some(DeploymentRef::Subgraph(subgraph)), _) | (None, Some(subgraph)) => subgraph,
+            (None, None) => return Err(anyhow!("A deployment or subgraph ID is required")),
+        };
+
+        display_info("Subgraph", &subgraph.to_string());
+        let resolved = network_subgraph
+            .graph_client()?
+            .resolve_subgraph(subgraph, self.version)
+            .await?;
+        let version = if resolved.current {
+            format!("{} (current)", resolved.version)
+        } else {
+            resolved.version.to_string()
+        };
+        display_info("Version", &version);
+        display_deployment(&resolved.deployment);
+        Ok(resolved.deployment)
+    }
+}
+
+/// Options selecting the network subgraph used to look up allocations.
+#[derive(Debug, Clone, Args)]
+pub struct NetworkSubgraphArgs {
+    #[arg(
+        long,
+        help = "Graph protocol network whose network subgraph is queried",
+        value_enum,
+        default_value = "arbitrum-one"
+    )]
+    pub graph_network: GraphNetwork,
+
+    #[arg(
+        long,
+        help = "Network subgraph URL, overriding --graph-network (e.g. a self-hosted graph-node)",
+        env = "NETWORK_SUBGRAPH_URL"
+    )]
+    pub network_subgraph_url: Option<String>,
+
+    #[arg(
+        long,
+        help = "API key for The Graph, only sent to its gateway",
+        env = "GRAPH_API_KEY"
+    )]
+    pub api_key: Option<String>,
+
+    #[arg(
+        long,
+        help = "Also query indexers whose allocation closed within this many epochs or this duration (e.g. 3, 12h, 7d)"
+    )]
+    pub include_closed_since: Option<ClosedSince>,
+
+    #[arg(
+        long,
+        help = "Epoch Block Oracle subgraph URL used to resolve epochs to blocks",
+        env = "EPOCH_ORACLE_URL"
+    )]
+    pub epoch_oracle_url: Option<String>,
+}
+
+impl NetworkSubgraphArgs {
+    pub fn graph_client(&self) -> Result<GraphClient> {
+        match (&self.network_subgraph_url, &self.api_key) {
+            (Some(url), _) => GraphClient::new(url.clone(), self.api_key_for(url)),
+            (None, Some(api_key)) => GraphClient::for_network(self.graph_network, api_key.clone()),
+            (None, None) => Err(anyhow!(
+                "An API key is required to query the network subgraph through the gateway. \
+                 Pass --api-key (or set GRAPH_API_KEY), or use --network-subgraph-url"
+            )),
+        }
+    }
+
+    /// Resolves `epoch` to its first block on `network` through the Epoch
+    /// Block Oracle.
+    pub async fn epoch_start_block(
+        &self,
+        epoch: EpochSelector,
+        network: &str,
+        registry: &RegistryArgs,
+    ) -> Result<u32> {
+        let oracle = self.epoch_oracle_client()?;
+        let caip2_id = registry.caip2_id(network).await;
+
+        let (epoch, block) = oracle
+            .epoch_start_block(epoch, network, caip2_id.as_deref())
+            .await?;
+        display_info(
+            &format!("Epoch {}", epoch),
+            &format!("starts at block {}", block),
+        );
+        Ok(block)
+    }
+
+    /// Resolves each of `epochs` to its first block on `network` through the
+    /// Epoch Block Oracle. Epochs the oracle has no block for are left out
+    /// with a warning.
+    pub async fn epoch_start_blocks(
+        &self,
+        epochs: &[u64],
+        network: &str,
+        registry: &RegistryArgs,
+    ) -> Result<BTreeMap<u64, u32>> {
+        let oracle = self.epoch_oracle_client()?;
+        let caip2_id = registry.caip2_id(network).await;
+
+        let mut blocks = BTreeMap::new();
+        for epoch in epochs {
+            match oracle
+                .epoch_start_block(EpochSelector::Number(*epoch), network, caip2_id.as_deref())
+                .await
+            {
+                Ok((epoch, block)) => {
+                    blocks.insert(epoch, block);
+                }
+                Err(e) => display_warning(&e.to_string()),
+            }
+        }
+        Ok(blocks)
+    }
+
+    /// The API key to send to a user-supplied `url`. The key often comes from
+    /// the environment, so it only goes to the gateway and never to e.g. a
+    /// self-hosted graph-node.
+    fn api_key_for(&self, url: &str) -> Option<String> {
+        self.api_key.clone().filter(|_| is_gateway_url(url))
+    }
+
+    pub fn epoch_oracle_client(&self) -> Result<EpochOracleClient> {
+        match (&self.epoch_oracle_url, &self.api_key) {
+            (Some(url), _) => EpochOracleClient::new(url.clone(), self.api_key_for(url)),
+            (None, Some(api_key)) => {
+                EpochOracleClient::for_network(self.graph_network, api_key.clone())
+            }
+            (None, None) => Err(anyhow!(
+                "An API key is required to query the Epoch Block Oracle through the gateway. \
+                 Pass --api-key (or set GRAPH_API_KEY), or use --epoch-oracle-url"
+            )),
+        }
+    }
+}
+
+/// An RPC URL given on the command line, optionally scoped to one network
+/// with a `<network>=` prefix.
+#[derive(Debug, Clone)]
+pub struct RpcUrl {
+    pub network: Option<String>,
+    pub url: String,
+}
+
+impl FromStr for RpcUrl {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (network, url) = match s.split_once('=') {
+            Some((network, url)) if !network.contains(['/', ':']) => {
+                (Some(network.to_string()), url)
+            }
+            _ => (None, s),
+        };
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(format!(
+                "Invalid RPC URL '{}': expected http(s)://... or <network>=http(s)://...",
+                s
+            ));
+        }
+        Ok(Self {
+            network,
+            url: url.to_string(),
+        })
+    }
+}
+
+/// Options selecting where a chain's head block is read from.
+#[derive(Debug, Clone, Args)]
+pub struct ChainHeadArgs {
+    #[arg(
+        long,
+        value_delimiter = ',',
+        env = "RPC_URL",
+        help = "RPC URL to read the chain head from, tried before the registry's public RPCs. \
+                Prefix with <network>= to use it for that network only (e.g. mainnet=https://...)"
+    )]
+    pub rpc_url: Vec<RpcUrl>,
+}
+
+impl ChainHeadArgs {
+    /// Connects to the first healthy RPC endpoint of `network`: user supplied
+    /// URLs first, network-specific ones before unscoped ones, then every
+    /// public RPC listed in the registry. Endpoints serving another chain
+    /// than the registry's chain ID for `network` are skipped.
+    pub async fn eth_client(&self, network: &str, registry: &RegistryArgs) -> Result<EthClient> {
+        let mut urls: Vec<String> = self
+            .rpc_url
+            .iter()
+            .filter(|rpc| rpc.network.as_deref() == Some(network))
+            .chain(self.rpc_url.iter().filter(|rpc| rpc.network.is_none()))
+            .map(|rpc| rpc.url.clone())
+            .collect();
+
+        // Without user supplied URLs the registry is required; with them it
+        // only adds fallbacks and the chain ID check
+        let chain_id = match registry.registry_client().await {
+            Ok(registry) => {
+                match registry.get_rpc_urls(network) {
+                    Ok(rpc_urls) => urls.extend(rpc_urls),
+                    Err(e) if urls.is_empty() => return Err(e),
+                    Err(_) => {}
+                }
+                registry.get_chain_id(network)
+            }
+            Err(e) if urls.is_empty() => return Err(e),
+            Err(_) => None,
+        };
+
+        EthClient::connect(&urls, chain_id).await
+    }
+
+    /// Reads the head block of `network`, from an RPC endpoint when one is
+    /// healthy and otherwise from the chain heads reported by `indexers`,
+    /// which are allocated to `deployment`, and displays which source was
+    /// used.
+    pub async fn chain_head(
+        &self,
+        network: &str,
+        deployment: &DeploymentId,
+        indexers: &BTreeMap<String, Indexer>,
+        registry: &RegistryArgs,
+    ) -> Result<u32> {
+        let mut providers = Vec::new();
+        match self.eth_client(network, registry).await {
+            Ok(eth_client) => providers.push(ChainHeadProvider::Rpc(eth_client)),
+            Err(e) => display_warning(&format!("No usable RPC endpoint for {}: {}", network, e)),
+        }
+        providers.push(ChainHeadProvider::Indexers {
+            indexers,
+            deployment,
+            network,
+        });
+
+        let (block, provider) = fetch_chain_head(&providers).await?;
+        display_info("Chain head source", &provider.to_string());
+        Ok(block)
+    }
+}
+
+/// Options selecting where the networks registry is loaded from.
+#[derive(Debug, Clone, Args)]
+pub struct RegistryArgs {
+    #[arg(
+        long,
+        env = "NETWORKS_REGISTRY_FILE",
+        help = "Networks registry JSON file to use instead of the cached or downloaded registry"
+    )]
+    pub registry_file: Option<PathBuf>,
+}
+
+impl RegistryArgs {
+    /// Loads the registry, warning when only a stale cache or the bundled
+    /// snapshot was available.
+    pub async fn registry_client(&self) -> Result<RegistryClient> {
+        let registry = match &self.registry_file {
+            Some(path) => RegistryClient::from_file(path)?,
+            None => RegistryClient::new().await?,
+        };
+        if matches!(
+            registry.source(),
+            RegistrySource::Cache { stale: true } | RegistrySource::Snapshot
+        ) {
+            display_warning(&format!(
+                "Networks registry could not be downloaded, using the {} (version {})",
+                registry.source(),
+                registry.version()
+            ));
+        }
+        Ok(registry)
+    }
+
+    /// The CAIP-2 ID of `network`, if the registry can be loaded and lists it.
+    /// The Epoch Block Oracle may list a chain under a different alias than
+    /// the manifest, so its CAIP-2 ID is matched as a fallback.
+    pub async fn caip2_id(&self, network: &str) -> Option<String> {
+        match self.registry_client().await {
+            Ok(registry) => registry.get_caip2_id(network).ok(),
+            Err(_) => None,
+        }
+    }
+}
+
+/// Options selecting where subgraph manifests and files are fetched from.
+#[derive(Debug, Clone, Args)]
+pub struct IpfsArgs {
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "IPFS API (.../api/v0) or gateway URLs to fetch subgraph manifests from, tried in order",
+        default_value = "https://ipfs.thegraph.com/ipfs/api/v0,https://ipfs.io"
+    )]
+    pub ipfs_url: Vec<IpfsEndpoint>,
+}
+
+impl IpfsArgs {
+    pub fn ipfs_client(&self) -> Result<IpfsClient> {
+        IpfsClient::new(self.ipfs_url.clone())
+    }
+}
@@ -0,0 +1,247 @@
+use anyhow::Result;
+use clap::Args;
+use colored::Colorize;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::client::poi::{POIClient, PoiError};
+use crate::client::{fetch_pois_from_indexers, resolve_reference_poi};
+use crate::commands::args::{IpfsArgs, NetworkSubgraphArgs, RegistryArgs};
+use crate::models::{ClosingPoiLookup, ClosingPoiStatus, DeploymentId, Reference, ReferencePoi};
+use crate::utils::{
+    display_closing_pois, display_deployment, display_header, display_info, display_success,
+    display_warning,
+};
+
+#[derive(Debug, Args)]
+pub struct ClosingPoisCommand {
+    #[arg(help = "Deployment ID (IPFS hash or 0x-prefixed bytes32)")]
+    deployment: DeploymentId,
+
+    #[command(flatten)]
+    network_subgraph: NetworkSubgraphArgs,
+
+    #[arg(long, help = "Weight the consensus majority by indexer stake")]
+    stake_weighted: bool,
+
+    #[command(flatten)]
+    registry: RegistryArgs,
+
+    #[arg(long, help = "Max retries for fetching POIs", default_value = "3")]
+    max_retries: u32,
+
+    #[command(flatten)]
+    ipfs: IpfsArgs,
+}
+
+impl ClosingPoisCommand {
+    pub async fn execute(self) -> Result<()> {
+        display_header("Public POIs at Closing Blocks");
+        display_deployment(&self.deployment);
+
+        let closed_since = self.network_subgraph.include_closed_since;
+        let graph_client = self.network_subgraph.graph_client()?;
+
+        println!("\n{}", "Fetching closed allocations...".bright_cyan());
+        let allocations = graph_client
+            .fetch_closed_allocations(&self.deployment, closed_since)
+            .await?;
+
+        if allocations.is_empty() {
+            display_warning("No closed allocations found for this deployment");
+            return Ok(());
+        }
+        display_success(&format!("Found {} closed allocations", allocations.len()));
+
+        // Closing POIs are submitted for the first block of the closing epoch
+        // on the chain the deployment indexes, not the protocol chain
+        println!("{}", "Fetching manifest from IPFS...".bright_cyan());
+        let ipfs_client = self.ipfs.ipfs_client()?;
+        let manifest = ipfs_client
+            .fetch_manifest(self.deployment.ipfs_hash())
+            .await?;
+        let network = ipfs_client.resolve_network(&manifest).await?;
+        display_info("Network", &network);
+
+        println!("{}", "Fetching epoch start blocks...".bright_cyan());
+        let epochs: Vec<u64> = allocations
+            .iter()
+            .filter_map(|allocation| allocation.closed_at_epoch)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let start_blocks = self
+            .network_subgraph
+            .epoch_start_blocks(&epochs, &network, &self.registry)
+            .await?;
+
+        println!("{}", "Fetching indexers...".bright_cyan());
+        let mut indexers = graph_client
+            .fetch_indexers(&self.deployment, closed_since)
+            .await?;
+
+        let mut lookups = Vec::new();
+        for allocation in allocations {
+            let block = allocation
+                .closed_at_epoch
+                .and_then(|epoch| start_blocks.get(&epoch).copied());
+            lookups.push(ClosingPoiLookup {
+                allocation_id: allocation.id.clone(),
+                indexer_id: allocation.indexer.id.clone(),
+                closed_at_epoch: allocation.closed_at_epoch,
+                block,
+                closing_poi: allocation.poi.as_deref().map(str::to_lowercase),
+                public_poi: None,
+                consensus_poi: None,
+                status: ClosingPoiStatus::Matches,
+            });
+
+            // The allocation's indexer must be asked for its own public POI
+            if let Some(indexer) = allocation.into_indexer() {
+                indexers.entry(indexer.id.clone()).or_insert(indexer);
+            }
+        }
+
+        let blocks: Vec<u32> = lookups
+            .iter()
+            .filter_map(|lookup| lookup.block)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+
+        println!(
+            "{}",
+            format!(
+                "Fetching public POIs at {} block(s) from {} indexers...",
+                blocks.len(),
+                indexers.len()
+            )
+            .bright_cyan()
+        );
+
+        let poi_client = POIClient::new()?;
+        let results = fetch_pois_from_indexers(
+            poi_client,
+            &indexers,
+            self.deployment.ipfs_hash(),
+            &blocks,
+            self.max_retries,
+        )
+        .await;
+
+        let reference = Reference::Consensus {
+            stake_weighted: self.stake_weighted,
+        };
+
+        let mut consensus: BTreeMap<u32, ReferencePoi> = BTreeMap::new();
+        for (i, block) in blocks.iter().enumerate() {
+            let block_pois: BTreeMap<&String, &Result<String, PoiError>> =
+                results.iter().map(|(id, pois)| (id, &pois[i])).collect();
+            consensus.insert(
+                *block,
+                resolve_reference_poi(&reference, &indexers, &block_pois),
+            );
+        }
+
+        for lookup in &mut lookups {
+            let Some(block) = lookup.block else {
+                lookup.status =
+                    ClosingPoiStatus::Inconclusive("Closing epoch start block unknown".to_string());
+                continue;
+            };
+
+            let index = blocks
+                .binary_search(&block)
+                .expect("every closing block is fetched");
+            let public_poi = results
+                .get(&lookup.indexer_id)
+                .map(|pois| pois[index].clone())
+                .unwrap_or_else(|| Err(PoiError::InvalidUrl("Indexer has no URL".to_string())));
+            let consensus_poi = &consensus[&block];
+
+            lookup.public_poi = public_poi.as_ref().ok().map(|poi| poi.to_lowercase());
+            lookup.consensus_poi = consensus_poi.poi().map(str::to_lowercase);
+            lookup.status = closing_poi_status(&public_poi, consensus_poi);
+        }
+
+        display_closing_pois(&lookups);
+
+        Ok(())
+    }
+}
+
+/// Compares the public POI an allocation's indexer serves at the closing
+/// block with the consensus public POI there. Both are derived with the zero
+/// address, unlike the closing POI itself, so they can be compared directly.
+fn closing_poi_status(
+    public_poi: &Result<String, PoiError>,
+    consensus: &ReferencePoi,
+) -> ClosingPoiStatus {
+    let public_poi = match public_poi {
+        Ok(poi) => poi,
+        Err(e) => return ClosingPoiStatus::NotServed(e.to_string()),
+    };
+
+    match consensus {
+        ReferencePoi::Resolved(poi) if poi.eq_ignore_ascii_case(public_poi) => {
+            ClosingPoiStatus::Matches
+        }
+        ReferencePoi::Resolved(_) => ClosingPoiStatus::Diverged,
+        ReferencePoi::Unavailable(reason) => ClosingPoiStatus::Inconclusive(reason.clone()),
+        ReferencePoi::Conflict(_) => ClosingPoiStatus::Inconclusive("No consensus POI".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn consensus(poi: &str) -> ReferencePoi {
+        ReferencePoi::Resolved(poi.to_string())
+    }
+
+    #[test]
+    fn public_poi_equal_to_consensus_matches() {
+        let public_poi = Ok("0xABCD".to_string());
+        assert_eq!(
+            closing_poi_status(&public_poi, &consensus("0xabcd")),
+            ClosingPoiStatus::Matches
+        );
+    }
+
+    #[test]
+    fn public_poi_outside_consensus_diverged() {
+        let public_poi = Ok("0x1234".to_string());
+        assert_eq!(
+            closing_poi_status(&public_poi, &consensus("0xabcd")),
+            ClosingPoiStatus::Diverged
+        );
+    }
+
+    #[test]
+    fn missing_public_poi_is_not_served() {
+        let public_poi = Err(PoiError::NotIndexed {
+            deployment: "Qm".to_string(),
+            block: 21_000_000,
+        });
+        assert_eq!(
+            closing_poi_status(&public_poi, &consensus("0xabcd")),
+            ClosingPoiStatus::NotServed(
+                "Block 21000000 not indexed (not synced or pruned)".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn missing_consensus_is_inconclusive() {
+        let public_poi = Ok("0x1234".to_string());
+        let unavailable = ReferencePoi::Unavailable("No POI has a strict majority".to_string());
+        assert_eq!(
+            closing_poi_status(&public_poi, &unavailable),
+            ClosingPoiStatus::Inconclusive("No POI has a strict majority".to_string())
+        );
+        assert_eq!(
+            closing_poi_status(&public_poi, &ReferencePoi::Conflict(BTreeMap::new())),
+            ClosingPoiStatus::Inconclusive("No consensus POI".to_string())
+        );
+    }
+}
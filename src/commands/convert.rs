@@ -0,0 +1,21 @@
+use anyhow::Result;
+use clap::Args;
+
+use crate::models::DeploymentId;
+use crate::utils::{display_header, display_info};
+
+#[derive(Debug, Args)]
+pub struct ConvertCommand {
+    #[arg(help = "Deployment ID (IPFS hash or 0x-prefixed bytes32)")]
+    deployment: DeploymentId,
+}
+
+impl ConvertCommand {
+    pub async fn execute(self) -> Result<()> {
+        display_header("Deployment ID");
+        display_info("IPFS hash", self.deployment.ipfs_hash());
+        display_info("Bytes32", &self.deployment.to_hex());
+
+        Ok(())
+    }
+}
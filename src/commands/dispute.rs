@@ -0,0 +1,420 @@
+use anyhow::{anyhow, Result};
+use clap::Args;
+use colored::Colorize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::client::epoch::EpochSelector;
+use crate::client::poi::{POIClient, PoiError};
+use crate::client::{fetch_pois_from_indexers, resolve_reference_poi, QueryRecorder};
+use crate::commands::args::{IpfsArgs, NetworkSubgraphArgs, RegistryArgs};
+use crate::models::{
+    BlockEvidence, DeploymentId, DisputeBundle, DisputedAllocation, Indexer, Reference,
+};
+use crate::utils::{
+    display_deployment, display_error, display_header, display_info, display_success,
+    display_warning, group_pois_by_hash,
+};
+
+#[derive(Debug, Args)]
+pub struct DisputeBundleCommand {
+    #[arg(help = "Deployment ID (IPFS hash or 0x-prefixed bytes32)")]
+    deployment: DeploymentId,
+
+    #[arg(long, help = "Indexer the dispute is filed against")]
+    indexer: String,
+
+    #[arg(long, help = "First diverged block, as reported by check-divergence")]
+    block: u32,
+
+    #[arg(
+        long,
+        help = "Disputed allocation ID (defaults to the indexer's latest closed allocation)"
+    )]
+    allocation: Option<String>,
+
+    #[arg(
+        long,
+        short,
+        help = "Output file (defaults to dispute-<indexer>-<block>.json)"
+    )]
+    output: Option<String>,
+
+    #[arg(long, help = "Overwrite the output file if it already exists")]
+    force: bool,
+
+    #[arg(long, help = "Weight the consensus majority by indexer stake")]
+    stake_weighted: bool,
+
+    #[command(flatten)]
+    network_subgraph: NetworkSubgraphArgs,
+
+    #[command(flatten)]
+    registry: RegistryArgs,
+
+    #[arg(long, help = "Max retries for fetching POIs", default_value = "3")]
+    max_retries: u32,
+
+    #[command(flatten)]
+    ipfs: IpfsArgs,
+}
+
+impl DisputeBundleCommand {
+    pub async fn execute(self) -> Result<()> {
+        display_header("Dispute Evidence Bundle");
+        display_deployment(&self.deployment);
+        display_info("Indexer", &self.indexer);
+        display_info("Diverged Block", &self.block.to_string());
+
+        let indexer_id = self.indexer.to_lowercase();
+        let output = self
+            .output
+            .clone()
+            .unwrap_or_else(|| format!("dispute-{}-{}.json", indexer_id, self.block));
+        // Checked up front so no queries are wasted; write_bundle checks again
+        if !self.force && Path::new(&output).exists() {
+            return Err(already_exists(&output));
+        }
+
+        let recorder = QueryRecorder::new();
+        let graph_client = self
+            .network_subgraph
+            .graph_client()?
+            .with_recorder(recorder.clone());
+        let poi_client = POIClient::new()?.with_recorder(recorder.clone());
+
+        println!("\n{}", "Fetching allocations...".bright_cyan());
+        let closed_since = self.network_subgraph.include_closed_since;
+        let mut indexers = graph_client
+            .fetch_indexers(&self.deployment, closed_since)
+            .await?;
+
+        let mut allocations: Vec<_> = graph_client
+            .fetch_closed_allocations(&self.deployment, closed_since)
+            .await?
+            .into_iter()
+            .filter(|allocation| allocation.indexer.id == indexer_id)
+            .collect();
+        allocations.sort_by_key(|allocation| allocation.closed_at_epoch);
+
+        let allocation = match &self.allocation {
+            Some(id) => {
+                let id = id.to_lowercase();
+                let position = allocations
+                    .iter()
+                    .position(|allocation| allocation.id == id)
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "Allocation {} is not a closed allocation of {}",
+                            id,
+                            indexer_id
+                        )
+                    })?;
+                Some(allocations.swap_remove(position))
+            }
+            None => allocations.pop(),
+        };
+
+        let allocation = match allocation {
+            Some(allocation) => {
+                let (network, epoch_start_block) = match allocation.closed_at_epoch {
+                    Some(epoch) => {
+                        let (network, block) = self.epoch_start_block(epoch, &recorder).await?;
+                        (Some(network), Some(block))
+                    }
+                    None => (None, None),
+                };
+                display_success(&format!("Disputed allocation: {}", allocation.id));
+
+                let disputed = DisputedAllocation {
+                    id: allocation.id.clone(),
+                    closed_at_epoch: allocation.closed_at_epoch,
+                    manifest: network.as_ref().map(|_| self.deployment.to_string()),
+                    network,
+                    epoch_start_block,
+                    submitted_poi: allocation.poi.clone(),
+                };
+                if let Some(indexer) = allocation.into_indexer() {
+                    indexers.entry(indexer.id.clone()).or_insert(indexer);
+                }
+                Some(disputed)
+            }
+            None => {
+                display_warning(
+                    "No closed allocation found; the bundle will hold public POIs only",
+                );
+                None
+            }
+        };
+
+        if !indexers.contains_key(&indexer_id) {
+            return Err(anyhow!(
+                "Indexer {} has no allocation with a service URL on this deployment",
+                indexer_id
+            ));
+        }
+
+        // The block before the divergence shows the indexer still agreed;
+        // the epoch start block is where the closing POI was submitted
+        let mut blocks = BTreeSet::from([self.block]);
+        if let Some(last_match) = self.block.checked_sub(1) {
+            blocks.insert(last_match);
+        }
+        if let Some(block) = allocation.as_ref().and_then(|a| a.epoch_start_block) {
+            blocks.insert(block);
+        }
+        let blocks: Vec<u32> = blocks.into_iter().collect();
+
+        println!(
+            "{}",
+            format!(
+                "Fetching POIs at {} block(s) from {} indexers...",
+                blocks.len(),
+                indexers.len()
+            )
+            .bright_cyan()
+        );
+        let results = fetch_pois_from_indexers(
+            poi_client,
+            &indexers,
+            self.deployment.ipfs_hash(),
+            &blocks,
+            self.max_retries,
+        )
+        .await;
+
+        let reference = Reference::Consensus {
+            stake_weighted: self.stake_weighted,
+        };
+
+        let evidence = collect_evidence(&blocks, &results, &indexers, &reference, &indexer_id);
+
+        if let Some(diverged) = evidence.iter().find(|e| e.block == self.block) {
+            match (&diverged.indexer_poi, &diverged.consensus_poi) {
+                (Some(poi), Some(consensus)) if poi != consensus => display_error(&format!(
+                    "Indexer POI differs from consensus at block {}",
+                    self.block
+                )),
+                (Some(_), Some(_)) => display_warning(&format!(
+                    "Indexer POI matches consensus at block {}",
+                    self.block
+                )),
+                _ => display_warning(&format!(
+                    "Could not compare the indexer's POI with consensus at block {}",
+                    self.block
+                )),
+            }
+        }
+
+        let bundle = DisputeBundle {
+            generated_at: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+            deployment: self.deployment.to_string(),
+            deployment_bytes32: self.deployment.to_hex(),
+            indexer: indexer_id.clone(),
+            reference: if self.stake_weighted {
+                "stake-weighted consensus".to_string()
+            } else {
+                "consensus".to_string()
+            },
+            diverged_block: self.block,
+            allocation,
+            blocks: evidence,
+            queries: recorder.queries(),
+        };
+
+        write_bundle(&output, &serde_json::to_string_pretty(&bundle)?, self.force)?;
+
+        display_info("Recorded queries", &bundle.queries.len().to_string());
+        display_success(&format!("Dispute bundle written to {}", output));
+
+        Ok(())
+    }
+
+    /// Resolves `epoch` to the deployment's chain and its first block there,
+    /// where the closing POI was submitted, recording the Epoch Block Oracle
+    /// query. The chain comes from the manifest, which is addressed by the
+    /// deployment ID and so need not be recorded.
+    async fn epoch_start_block(
+        &self,
+        epoch: u64,
+        recorder: &QueryRecorder,
+    ) -> Result<(String, u32)> {
+        let ipfs_client = self.ipfs.ipfs_client()?;
+        let manifest = ipfs_client
+            .fetch_manifest(self.deployment.ipfs_hash())
+            .await?;
+        let network = ipfs_client.resolve_network(&manifest).await?;
+        display_info("Network", &network);
+
+        let oracle = self
+            .network_subgraph
+            .epoch_oracle_client()?
+            .with_recorder(recorder.clone());
+        let caip2_id = self.registry.caip2_id(&network).await;
+        let (_, block) = oracle
+            .epoch_start_block(EpochSelector::Number(epoch), &network, caip2_id.as_deref())
+            .await?;
+        display_info(
+            &format!("Epoch {}", epoch),
+            &format!("starts at block {}", block),
+        );
+        Ok((network, block))
+    }
+}
+
+/// Groups the POIs fetched at each of `blocks` and sets the disputed
+/// indexer's own result against the consensus POI.
+fn collect_evidence(
+    blocks: &[u32],
+    results: &BTreeMap<String, Vec<Result<String, PoiError>>>,
+    indexers: &BTreeMap<String, Indexer>,
+    reference: &Reference,
+    indexer_id: &String,
+) -> Vec<BlockEvidence> {
+    blocks
+        .iter()
+        .enumerate()
+        .map(|(i, block)| {
+            let block_pois: BTreeMap<&String, &Result<String, PoiError>> =
+                results.iter().map(|(id, pois)| (id, &pois[i])).collect();
+            let consensus = resolve_reference_poi(reference, indexers, &block_pois);
+            let pois: Vec<(String, String)> = block_pois
+                .iter()
+                .filter_map(|(id, poi)| poi.as_ref().ok().map(|poi| ((*id).clone(), poi.clone())))
+                .collect();
+            let indexer_result = block_pois.get(indexer_id);
+
+            BlockEvidence {
+                block: *block,
+                consensus_poi: consensus.poi().map(str::to_string),
+                indexer_poi: indexer_result.and_then(|poi| poi.as_ref().ok().cloned()),
+                indexer_error: indexer_result
+                    .and_then(|poi| poi.as_ref().err().map(|e| e.to_string())),
+                groups: group_pois_by_hash(indexers, &pois, consensus.poi()),
+            }
+        })
+        .collect()
+}
+
+/// Writes the bundle to `path`, refusing to replace an existing file unless
+/// `force` is set.
+fn write_bundle(path: &str, json: &str, force: bool) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true);
+    if force {
+        options.create(true).truncate(true);
+    } else {
+        options.create_new(true);
+    }
+
+    let mut file = options.open(path).map_err(|e| match e.kind() {
+        ErrorKind::AlreadyExists => already_exists(path),
+        _ => anyhow!("Failed to create {}: {}", path, e),
+    })?;
+    file.write_all(json.as_bytes())?;
+    Ok(())
+}
+
+fn already_exists(path: &str) -> anyhow::Error {
+    anyhow!("{} already exists; pass --force to overwrite it", path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::AllocationStatus;
+
+    fn indexers(ids: &[&str]) -> BTreeMap<String, Indexer> {
+        ids.iter()
+            .map(|id| {
+                let indexer = Indexer {
+                    id: id.to_string(),
+                    url: format!("https://{}.example.com/", id),
+                    staked_tokens: None,
+                    allocation_ids: Vec::new(),
+                    allocation_status: AllocationStatus::Closed,
+                };
+                (id.to_string(), indexer)
+            })
+            .collect()
+    }
+
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!(
+            "poipal-dispute-{}-{}.json",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_file(&path);
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn evidence_sets_the_indexer_against_consensus() {
+        let indexers = indexers(&["0xa", "0xb", "0xdisputed"]);
+        let results = BTreeMap::from([
+            (
+                "0xa".to_string(),
+                vec![Ok("0x1".to_string()), Ok("0x2".to_string())],
+            ),
+            (
+                "0xb".to_string(),
+                vec![Ok("0x1".to_string()), Ok("0x2".to_string())],
+            ),
+            (
+                "0xdisputed".to_string(),
+                vec![Ok("0x1".to_string()), Err(PoiError::Timeout)],
+            ),
+        ]);
+        let reference = Reference::Consensus {
+            stake_weighted: false,
+        };
+
+        let evidence = collect_evidence(
+            &[99, 100],
+            &results,
+            &indexers,
+            &reference,
+            &"0xdisputed".to_string(),
+        );
+        assert_eq!(evidence.len(), 2);
+
+        assert_eq!(evidence[0].block, 99);
+        assert_eq!(evidence[0].consensus_poi.as_deref(), Some("0x1"));
+        assert_eq!(evidence[0].indexer_poi.as_deref(), Some("0x1"));
+        assert_eq!(evidence[0].groups.len(), 1);
+
+        assert_eq!(evidence[1].block, 100);
+        assert_eq!(evidence[1].consensus_poi.as_deref(), Some("0x2"));
+        assert_eq!(evidence[1].indexer_poi, None);
+        assert_eq!(
+            evidence[1].indexer_error.as_deref(),
+            Some("Request timed out")
+        );
+    }
+
+    #[test]
+    fn bundles_never_replace_existing_files() {
+        let path = temp_path("existing");
+        write_bundle(&path, "{}", false).unwrap();
+
+        let error = write_bundle(&path, "{\"replaced\":true}", false).unwrap_err();
+        assert!(error.to_string().contains("--force"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{}");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn force_overwrites_bundles() {
+        let path = temp_path("forced");
+        write_bundle(&path, "{\"first\":\"longer\"}", false).unwrap();
+        write_bundle(&path, "{}", true).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{}");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,47 +1,153 @@
 use anyhow::{anyhow, Result};
-use clap::Args;
+use clap::{Args, ValueEnum};
 use colored::Colorize;
-use std::collections::BTreeMap;
-use std::sync::Arc;
-use tokio::task::JoinSet;
-
-use crate::client::eth::EthClient;
-use crate::client::ipfs::IpfsClient;
-use crate::client::registry::RegistryClient;
-use crate::client::{check_divergence_at_block, poi::POIClient, subgraph::GraphClient};
-use crate::models::Indexer;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::RangeInclusive;
+
+use crate::client::epoch::EpochSelector;
+use crate::client::{
+    check_divergence_at_block, check_divergence_at_blocks, fetch_pois_from_indexers,
+    poi::{POIClient, PoiError},
+    resolve_reference_poi,
+};
+use crate::commands::args::{
+    ChainHeadArgs, DeploymentArgs, IpfsArgs, NetworkSubgraphArgs, RegistryArgs,
+};
+use crate::models::{
+    BlockCheck, DeploymentId, Indexer, IndexerDivergence, IndexerStatus, Reference, ReferencePoi,
+};
 use crate::utils::{
-    display_divergence_summary, display_error, display_header, display_info, display_poi_groups,
-    display_success, display_warning, group_pois_by_hash,
+    display_divergence_summary, display_error, display_graft_lineage, display_header,
+    display_indexer_divergences, display_indexers_found, display_info, display_poi_groups,
+    display_success, display_unresolved_indexers, display_warning, group_pois_by_hash,
 };
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReferenceMode {
+    /// Compare against the POI agreed on by the trusted `--indexer` list
+    Indexer,
+    /// Compare against the POI reported by a strict majority of indexers;
+    /// blocks where no POI has one have no reference
+    Consensus,
+}
+
+/// Most blocks probed in one per-indexer round. Every block is fetched from
+/// every indexer, so rounds would otherwise grow with indexers × fanout.
+const MAX_ROUND_PROBES: usize = 50;
+
+struct SearchOutcome {
+    diverged_block: Option<u32>,
+    /// Block at which the trusted indexers disagreed, stopping the search
+    reference_conflict: Option<u32>,
+    /// Range left unsearched because too many indexers were unavailable
+    unresolved_range: Option<(u32, u32)>,
+    /// Compared indexers that never returned a usable POI
+    never_resolved: Vec<String>,
+}
+
 #[derive(Debug, Args)]
 pub struct CheckDivergenceCommand {
-    #[arg(help = "Deployment ID (IPFS hash)")]
-    deployment: String,
+    #[command(flatten)]
+    target: DeploymentArgs,
 
     #[arg(long, help = "Start block for binary search")]
     start_block: Option<u32>,
 
+    #[arg(
+        long,
+        help = "Start the search at the first block of this epoch (number or 'current')",
+        conflicts_with = "start_block"
+    )]
+    start_epoch: Option<EpochSelector>,
+
     #[arg(long, help = "End block for binary search")]
     end_block: Option<u32>,
 
-    #[arg(long, help = "Indexer ID with correct POI", env = "TRUSTED_INDEXER")]
-    indexer: String,
+    #[arg(
+        long,
+        help = "End the search at the first block of this epoch (number or 'current')",
+        conflicts_with = "end_block"
+    )]
+    end_epoch: Option<EpochSelector>,
+
+    #[arg(
+        long,
+        help = "Trusted indexer ID(s) with correct POI (comma-separated)",
+        env = "TRUSTED_INDEXER",
+        value_delimiter = ','
+    )]
+    indexer: Option<Vec<String>>,
 
-    #[arg(long, help = "API key for The Graph", env = "GRAPH_API_KEY")]
-    api_key: String,
+    #[arg(
+        long,
+        help = "Number of trusted indexers that must agree on the reference POI; the one group reaching it outvotes the rest",
+        default_value = "1"
+    )]
+    quorum: usize,
 
     #[arg(
         long,
-        help = "IPFS base URL to fetch subgraph manifest",
-        default_value = "https://ipfs.thegraph.com"
+        value_enum,
+        help = "What POIs are compared against: the trusted indexer or the POI of a strict majority of indexers \
+                (a plurality is not enough; blocks without a majority are reported as having no reference)",
+        default_value = "indexer"
     )]
-    ipfs_url: String,
+    reference: ReferenceMode,
+
+    #[arg(long, help = "Weight the consensus majority by indexer stake")]
+    stake_weighted: bool,
+
+    #[command(flatten)]
+    network_subgraph: NetworkSubgraphArgs,
+
+    #[command(flatten)]
+    chain_head: ChainHeadArgs,
+
+    #[command(flatten)]
+    registry: RegistryArgs,
+
+    #[command(flatten)]
+    ipfs: IpfsArgs,
 
     #[arg(long, help = "Max retries for fetching POIs", default_value = "3")]
     max_retries: u32,
 
+    #[arg(
+        long,
+        help = "Number of evenly spaced blocks to probe in parallel per search round",
+        default_value = "1",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    fanout: u32,
+
+    #[arg(
+        long,
+        help = "Max share of unavailable indexers (0.0-1.0) before a matching block is treated as inconclusive",
+        default_value = "0.5",
+        value_parser = parse_share
+    )]
+    max_unavailable: f64,
+
+    #[arg(
+        long,
+        help = "Neighbouring blocks to re-probe when a block is inconclusive",
+        default_value = "2"
+    )]
+    reprobe_attempts: u32,
+
+    #[arg(
+        long,
+        help = "Find the last matching and first diverging block of every indexer"
+    )]
+    per_indexer: bool,
+
+    #[arg(
+        long,
+        help = "Continue the search into the base deployment when the divergence predates the graft point. \
+                The same indexers are searched there; pass --include-closed-since when they no longer allocate to the base"
+    )]
+    follow_grafts: bool,
+
     #[arg(
         long,
         help = "Indexers to include for divergence check (check only these)",
@@ -50,79 +156,170 @@ pub struct CheckDivergenceCommand {
     only_indexers: Option<Vec<String>>,
 }
 
+/// Parses a share between 0.0 and 1.0 inclusive.
+fn parse_share(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(share) if (0.0..=1.0).contains(&share) => Ok(share),
+        _ => Err(format!("'{}' is not a share between 0.0 and 1.0", value)),
+    }
+}
+
 impl CheckDivergenceCommand {
     pub async fn execute(self) -> Result<()> {
         display_header("POI Divergence Checker");
-        display_info("Deployment", &self.deployment);
+        let deployment = self.target.resolve(&self.network_subgraph).await?;
+
+        if self.stake_weighted && self.reference != ReferenceMode::Consensus {
+            return Err(anyhow!("--stake-weighted requires --reference consensus"));
+        }
+
+        let reference = match self.reference {
+            ReferenceMode::Indexer => {
+                let trusted = self.indexer.clone().ok_or_else(|| {
+                    anyhow!("--indexer (or TRUSTED_INDEXER) is required unless --reference consensus is used")
+                })?;
+                if self.quorum == 0 || self.quorum > trusted.len() {
+                    return Err(anyhow!(
+                        "--quorum must be between 1 and the number of trusted indexers ({})",
+                        trusted.len()
+                    ));
+                }
+                Reference::Trusted {
+                    indexers: trusted,
+                    quorum: self.quorum,
+                }
+            }
+            ReferenceMode::Consensus => Reference::Consensus {
+                stake_weighted: self.stake_weighted,
+            },
+        };
+
+        // Fetched first, as their chain heads back up the RPC lookup
+        println!("\n{}", "Fetching active indexers...".bright_cyan());
+
+        let graph_client = self.network_subgraph.graph_client()?;
+        let mut indexers = graph_client
+            .fetch_indexers(&deployment, self.network_subgraph.include_closed_since)
+            .await?;
+
+        for indexer in self.indexer.iter().flatten() {
+            if !indexers.contains_key(indexer) {
+                display_error(&format!(
+                    "Reference indexer '{}' not found in active allocations",
+                    indexer
+                ));
+                return Err(anyhow!("Invalid reference indexer"));
+            }
+        }
+
+        display_indexers_found(&indexers);
+
+        let ipfs_client = self.ipfs.ipfs_client()?;
 
-        let ipfs_url = self.ipfs_url.clone();
-        let ipfs_client = IpfsClient::new(ipfs_url)?;
+        // The lineage gives the search bounds, and the graft point that
+        // --follow-grafts continues from
+        let lineage =
+            if self.follow_grafts || self.start_block.is_none() || self.end_block.is_none() {
+                let lineage = ipfs_client
+                    .fetch_graft_lineage(deployment.ipfs_hash())
+                    .await?;
+                display_graft_lineage(&lineage);
+                lineage
+            } else {
+                Vec::new()
+            };
+        let manifest = lineage.first().map(|entry| &entry.manifest);
+        let graft = manifest.and_then(|manifest| manifest.graft.clone());
 
-        let manifest = if self.start_block.is_none() || self.end_block.is_none() {
-            Some(ipfs_client.fetch_manifest(&self.deployment).await?)
+        // Epoch bounds are resolved on the manifest's network
+        let network = if self.start_epoch.is_some() || self.end_epoch.is_some() {
+            println!("\n{}", "Fetching network from manifest...".bright_cyan());
+            let network = ipfs_client.resolve_network(manifest.unwrap()).await?;
+            display_info("Network", &network);
+            Some(network)
         } else {
             None
         };
 
-        let start_block = match &self.start_block {
-            Some(start_block) => *start_block,
-            None => {
+        let start_block = match (&self.start_block, self.start_epoch, &network) {
+            (Some(start_block), _, _) => *start_block,
+            (None, Some(epoch), Some(network)) => {
+                self.network_subgraph
+                    .epoch_start_block(epoch, network, &self.registry)
+                    .await?
+            }
+            _ => {
                 println!("\n{}", "Fetching start block from IPFS...".bright_cyan());
-                let block = ipfs_client
-                    .get_start_block(manifest.as_ref().unwrap())
-                    .await?;
+                // Before the graft point, POIs come from the base deployment's history
+                let block = match &graft {
+                    Some(graft) => graft.block,
+                    None => ipfs_client.resolve_start_block(manifest.unwrap()).await?,
+                };
                 display_success(&format!("Fetched start block: {}", block));
                 block
             }
         };
 
-        let end_block = match &self.end_block {
-            Some(end_block) => *end_block,
-            None => {
-                println!("\n{}", "Fetching network from manifest...".bright_cyan());
-                let network = ipfs_client
-                    .get_network(manifest.as_ref().unwrap())
+        let end_block = match (&self.end_block, self.end_epoch, &network) {
+            (Some(end_block), _, _) => *end_block,
+            (None, Some(epoch), Some(network)) => {
+                self.network_subgraph
+                    .epoch_start_block(epoch, network, &self.registry)
                     .await?
-                    .ok_or_else(|| anyhow!("Network not found in manifest"))?;
+            }
+            _ => {
+                println!("\n{}", "Fetching network from manifest...".bright_cyan());
+                let network = ipfs_client.resolve_network(manifest.unwrap()).await?;
                 display_info("Network", &network);
 
-                println!("{}", "Fetching RPC URL from registry...".bright_cyan());
-                let registry_client = RegistryClient::new().await?;
-                let rpc_url = registry_client.get_public_rpc_url(&network).await?;
-                display_info("RPC URL", &rpc_url);
-
                 println!("{}", "Fetching chain head block...".bright_cyan());
-                let eth_client = EthClient::new(rpc_url)?;
-                let block = eth_client.get_chain_head_block_number().await?;
+                let block = self
+                    .chain_head
+                    .chain_head(&network, &deployment, &indexers, &self.registry)
+                    .await?;
                 display_success(&format!("Fetched end block: {}", block));
                 block
             }
         };
 
         display_info("Search Range", &format!("{} → {}", start_block, end_block));
-        display_info("Reference Indexer", &self.indexer);
-
-        println!("\n{}", "Fetching active indexers...".bright_cyan());
 
-        let graph_client = GraphClient::new(self.api_key.clone())?;
-        let mut indexers = graph_client.fetch_indexers(&self.deployment).await?;
-
-        if !indexers.contains_key(&self.indexer) {
-            display_error(&format!(
-                "Reference indexer '{}' not found in active allocations",
-                self.indexer
+        if let Some(graft) = graft.as_ref().filter(|graft| start_block < graft.block) {
+            display_warning(&format!(
+                "Blocks before {} were copied from the base deployment {}",
+                graft.block, graft.base
             ));
-            return Err(anyhow!("Invalid reference indexer"));
         }
 
-        display_success(&format!("Found {} active indexers", indexers.len()));
+        match &reference {
+            Reference::Trusted { indexers, quorum } if indexers.len() > 1 => {
+                display_info("Trusted Indexers", &indexers.join(", "));
+                display_info("Quorum", &format!("{}/{}", quorum, indexers.len()));
+            }
+            Reference::Trusted { indexers, .. } => {
+                display_info("Reference Indexer", &indexers.join(", "))
+            }
+            Reference::Consensus { stake_weighted } => {
+                let mode = if *stake_weighted {
+                    "Stake-weighted majority"
+                } else {
+                    "Majority"
+                };
+                display_info("Reference", mode);
+                if let Some(watched) = &self.indexer {
+                    display_info("Watched Indexers", &watched.join(", "));
+                }
+            }
+        }
 
         if let Some(ref include_list) = self.only_indexers {
             let initial_count = indexers.len();
             let mut include_list_with_ref = include_list.clone();
 
-            if !include_list_with_ref.contains(&self.indexer) {
-                include_list_with_ref.push(self.indexer.clone());
+            for indexer in self.indexer.iter().flatten() {
+                if !include_list_with_ref.contains(indexer) {
+                    include_list_with_ref.push(indexer.clone());
+                }
             }
             indexers.retain(|id, _| include_list_with_ref.contains(id));
             let filtered_count = initial_count - indexers.len();
@@ -132,33 +329,156 @@ impl CheckDivergenceCommand {
             }
         }
 
-        if indexers.len() == 1 && indexers.contains_key(&self.indexer) {
+        if indexers.len() <= 1 {
             display_success("Only one active indexer remaining, no divergence possible");
             return Ok(());
         }
 
         let poi_client = POIClient::new()?;
 
+        if self.per_indexer {
+            println!(
+                "\n{}",
+                "Starting per-indexer search for diverged blocks...".bright_cyan()
+            );
+            println!("{}", "─".repeat(60).bright_black());
+
+            let (divergences, reference_conflict) = self
+                .find_diverged_blocks_per_indexer(
+                    deployment.ipfs_hash(),
+                    &poi_client,
+                    &indexers,
+                    &reference,
+                    start_block,
+                    end_block,
+                )
+                .await?;
+            display_indexer_divergences(&divergences, start_block, end_block);
+
+            if let Some(block) = reference_conflict {
+                self.display_reference_conflict(
+                    deployment.ipfs_hash(),
+                    poi_client,
+                    &indexers,
+                    &reference,
+                    block,
+                )
+                .await?;
+            }
+            return Ok(());
+        }
+
         println!(
             "\n{}",
             "Starting binary search for diverged block...".bright_cyan()
         );
         println!("{}", "─".repeat(60).bright_black());
 
-        match self
-            .find_diverged_block(&poi_client, &indexers, start_block, end_block)
-            .await?
-        {
-            Some(block) => {
+        let outcome = self
+            .find_diverged_block(
+                deployment.ipfs_hash(),
+                &poi_client,
+                &indexers,
+                &reference,
+                start_block,
+                end_block,
+            )
+            .await?;
+
+        if let Some(block) = outcome.reference_conflict {
+            if let Some(diverged_block) = outcome.diverged_block {
+                display_divergence_summary(true, Some(diverged_block), start_block, end_block);
+            }
+            self.display_reference_conflict(
+                deployment.ipfs_hash(),
+                poi_client,
+                &indexers,
+                &reference,
+                block,
+            )
+            .await?;
+            return Ok(());
+        }
+
+        match (outcome.diverged_block, outcome.unresolved_range) {
+            (Some(block), unresolved_range) => {
                 display_divergence_summary(true, Some(block), start_block, end_block);
+                if let Some((left, right)) = unresolved_range {
+                    display_warning(&format!(
+                        "Too many indexers unavailable to narrow further: an earlier divergence may lie between blocks {} and {}",
+                        left, right
+                    ));
+                }
+                display_unresolved_indexers(&outcome.never_resolved);
 
                 println!("\n{}", "Fetching POIs at diverged block...".bright_cyan());
-                self.display_pois_at_block(poi_client, &indexers, block)
-                    .await?;
+                self.display_pois_at_block(
+                    deployment.ipfs_hash(),
+                    poi_client,
+                    &indexers,
+                    &reference,
+                    block,
+                )
+                .await?;
+
+                // A divergence already present at the graft point was
+                // inherited from the base deployment
+                if let Some(graft) = graft.filter(|graft| block <= graft.block) {
+                    if self.follow_grafts {
+                        println!(
+                            "\n{}",
+                            format!(
+                                "Divergence predates the graft point, continuing in base deployment {}...",
+                                graft.base
+                            )
+                            .bright_cyan()
+                        );
+                        let include_closed = self.network_subgraph.include_closed_since.is_some();
+                        let base = CheckDivergenceCommand {
+                            target: graft.base.parse::<DeploymentId>()?.into(),
+                            start_block: None,
+                            start_epoch: None,
+                            end_block: Some(graft.block),
+                            end_epoch: None,
+                            ..self
+                        };
+                        return Box::pin(base.execute()).await.map_err(|e| {
+                            if include_closed {
+                                anyhow!("Search in base deployment {} failed: {}", graft.base, e)
+                            } else {
+                                anyhow!(
+                                    "Search in base deployment {} failed: {}. Indexers usually close their \
+                                     allocations on a base deployment; rerun with --include-closed-since \
+                                     to search them",
+                                    graft.base,
+                                    e
+                                )
+                            }
+                        });
+                    }
+                    display_warning(&format!(
+                        "Divergence at the graft point may originate in base deployment {}; rerun with --follow-grafts to search it",
+                        graft.base
+                    ));
+                }
+            }
+            (None, Some((left, right))) => {
+                display_warning(&format!(
+                    "Search stopped: too many indexers unavailable. No divergence found before block {}, blocks {} to {} are unresolved",
+                    left, left, right
+                ));
+                display_unresolved_indexers(&outcome.never_resolved);
             }
-            None => {
+            (None, None) => {
                 display_divergence_summary(false, None, start_block, end_block);
-                display_success("All indexers have matching POIs in the specified range");
+                if outcome.never_resolved.is_empty() {
+                    display_success("All indexers have matching POIs in the specified range");
+                } else {
+                    display_success(
+                        "All resolved indexers have matching POIs in the specified range",
+                    );
+                    display_unresolved_indexers(&outcome.never_resolved);
+                }
             }
         }
 
@@ -167,96 +487,439 @@ impl CheckDivergenceCommand {
 
     async fn find_diverged_block(
         &self,
+        deployment: &str,
         poi_client: &POIClient,
         indexers: &BTreeMap<String, Indexer>,
+        reference: &Reference,
         start_block: u32,
         end_block: u32,
-    ) -> Result<Option<u32>> {
+    ) -> Result<SearchOutcome> {
         let mut left = start_block;
         let mut right = end_block;
         let mut diverged_block = None;
+        let mut reference_conflict = None;
+        let mut unresolved_range = None;
+        let mut resolved = BTreeSet::new();
 
         while left <= right {
-            let mid = left + (right - left) / 2;
+            let probes = probe_blocks(left, right, self.fanout);
+
+            if probes.len() == 1 {
+                print!(
+                    "{} Checking block {} (range: {} - {})... ",
+                    "→".bright_cyan(),
+                    probes[0].to_string().bright_white(),
+                    left.to_string().bright_black(),
+                    right.to_string().bright_black()
+                );
+            } else {
+                println!(
+                    "{} Checking {} blocks (range: {} - {})",
+                    "→".bright_cyan(),
+                    probes.len().to_string().bright_white(),
+                    left.to_string().bright_black(),
+                    right.to_string().bright_black()
+                );
+            }
+
+            let checks = check_divergence_at_blocks(
+                poi_client.clone(),
+                indexers,
+                deployment,
+                &probes,
+                reference,
+                self.max_retries,
+            )
+            .await?;
+
+            let mut next_left = left;
+            let mut first_divergence = None;
+            let mut inconclusive = false;
+
+            for (i, mut check) in checks.into_iter().enumerate() {
+                if probes.len() > 1 {
+                    print!("    block {} ", check.block.to_string().bright_white());
+                }
+                self.print_block_check(&check);
+
+                // A disagreement among trusted indexers is a result of its own
+                if check.reference.is_conflict() && first_divergence.is_none() {
+                    reference_conflict = Some(check.block);
+                    break;
+                }
+
+                if !check.is_conclusive(self.max_unavailable) {
+                    // Stay between the neighbouring probes so their ordering still holds
+                    let low = if i == 0 { left } else { probes[i - 1] + 1 };
+                    let high = probes.get(i + 1).map_or(right, |next| next - 1);
+                    check = self
+                        .reprobe(
+                            deployment,
+                            poi_client,
+                            indexers,
+                            reference,
+                            check,
+                            low..=high,
+                        )
+                        .await?;
+                }
+
+                resolved.extend(
+                    check
+                        .statuses
+                        .iter()
+                        .filter(|(_, status)| !matches!(status, IndexerStatus::Unavailable(_)))
+                        .map(|(id, _)| id.clone()),
+                );
+
+                if first_divergence.is_some() {
+                    continue;
+                }
+                if check.has_divergence() {
+                    first_divergence = Some(check.block);
+                } else if !check.is_conclusive(self.max_unavailable) {
+                    inconclusive = true;
+                } else if !inconclusive {
+                    next_left = check.block + 1;
+                }
+            }
+
+            left = next_left;
+            if reference_conflict.is_some() {
+                break;
+            } else if let Some(block) = first_divergence {
+                // Narrow to the sub-interval ending just before the first diverged probe
+                diverged_block = Some(block);
+                match block.checked_sub(1) {
+                    Some(block) => right = block,
+                    None => break,
+                }
+            } else if inconclusive {
+                unresolved_range = Some((left, right));
+                break;
+            }
+        }
+
+        let never_resolved = indexers
+            .keys()
+            .filter(|id| reference.is_compared(id) && !resolved.contains(*id))
+            .cloned()
+            .collect();
+
+        Ok(SearchOutcome {
+            diverged_block,
+            reference_conflict,
+            unresolved_range,
+            never_resolved,
+        })
+    }
+
+    /// Runs an independent search for every compared indexer. All probes of a
+    /// round are fetched from every indexer in one batch and cached, so a block
+    /// probed for one indexer also narrows the ranges of all the others. Rounds
+    /// are capped at [`MAX_ROUND_PROBES`] blocks; searches that do not fit wait.
+    async fn find_diverged_blocks_per_indexer(
+        &self,
+        deployment: &str,
+        poi_client: &POIClient,
+        indexers: &BTreeMap<String, Indexer>,
+        reference: &Reference,
+        start_block: u32,
+        end_block: u32,
+    ) -> Result<(Vec<IndexerDivergence>, Option<u32>)> {
+        let mut searches: BTreeMap<&String, IndexerSearch> = indexers
+            .keys()
+            .filter(|id| reference.is_compared(id))
+            .map(|id| (id, IndexerSearch::new(start_block, end_block)))
+            .collect();
+        let mut cache: BTreeMap<u32, BlockCheck> = BTreeMap::new();
+        let mut reference_conflict = None;
+
+        loop {
+            for (id, search) in searches.iter_mut() {
+                search.apply(id, &cache);
+            }
+            let (probes, scheduled) = schedule_round(&searches, &cache, self.fanout);
+
+            let active = searches
+                .values()
+                .filter(|search| search.is_active())
+                .count();
+            if probes.is_empty() {
+                break;
+            }
 
             print!(
-                "{} Checking block {} (range: {} - {})... ",
+                "{} Checking {} block(s) for {} unresolved indexer(s)... ",
                 "→".bright_cyan(),
-                mid.to_string().bright_white(),
-                left.to_string().bright_black(),
-                right.to_string().bright_black()
+                probes.len().to_string().bright_white(),
+                active.to_string().bright_white()
             );
 
-            let (has_divergence, diverged_indexers) = check_divergence_at_block(
+            let checks = check_divergence_at_blocks(
                 poi_client.clone(),
                 indexers,
-                &self.deployment,
-                mid,
-                &self.indexer,
+                deployment,
+                &probes,
+                reference,
                 self.max_retries,
             )
             .await?;
 
-            if has_divergence {
-                println!(
-                    "{} Divergence found ({} indexers)",
-                    "✗".red(),
-                    diverged_indexers.len().to_string().red()
-                );
-                diverged_block = Some(mid);
-                right = mid - 1;
+            let diverged: BTreeSet<&String> =
+                checks.iter().flat_map(|check| check.diverged()).collect();
+            let unavailable: BTreeSet<&String> = checks
+                .iter()
+                .flat_map(|check| check.unavailable())
+                .collect();
+            println!(
+                "{} diverged, {} unavailable",
+                diverged.len().to_string().red(),
+                unavailable.len().to_string().yellow()
+            );
+
+            if let Some(check) = checks.iter().find(|check| check.reference.is_conflict()) {
+                display_warning(&format!(
+                    "Trusted indexers disagree at block {}, stopping the search",
+                    check.block
+                ));
+                reference_conflict = Some(check.block);
+                break;
+            }
+
+            for (id, search) in searches.iter_mut() {
+                // Searches left out of the round had no chance to fail in it
+                if !search.is_active() || !scheduled.contains(id) {
+                    continue;
+                }
+                let resolved_any = checks.iter().any(|check| {
+                    (search.left..=search.right).contains(&check.block)
+                        && matches!(
+                            check.statuses.get(*id),
+                            Some(IndexerStatus::Match | IndexerStatus::Diverged)
+                        )
+                });
+                if !resolved_any {
+                    search.failed_rounds += 1;
+                }
+                if search.failed_rounds > self.reprobe_attempts {
+                    search.gave_up = true;
+                }
+            }
+
+            cache.extend(checks.into_iter().map(|check| (check.block, check)));
+        }
+
+        // Anything still open ran out of unprobed blocks to try
+        for search in searches.values_mut() {
+            if search.is_active() {
+                search.gave_up = true;
+            }
+        }
+
+        let divergences = searches
+            .into_iter()
+            .map(|(id, search)| IndexerDivergence {
+                indexer_id: id.clone(),
+                last_match: search.last_match,
+                first_divergence: search.first_divergence,
+                resolved: !search.gave_up,
+            })
+            .collect();
+
+        Ok((divergences, reference_conflict))
+    }
+
+    /// Re-probes blocks next to an inconclusive one, alternating above and
+    /// below it within `range`. Returns the first conclusive check, or the
+    /// original one if every attempt was inconclusive too.
+    async fn reprobe(
+        &self,
+        deployment: &str,
+        poi_client: &POIClient,
+        indexers: &BTreeMap<String, Indexer>,
+        reference: &Reference,
+        check: BlockCheck,
+        range: RangeInclusive<u32>,
+    ) -> Result<BlockCheck> {
+        let neighbours = (1..=self.reprobe_attempts)
+            .flat_map(|offset| {
+                [
+                    check.block.checked_add(offset),
+                    check.block.checked_sub(offset),
+                ]
+            })
+            .flatten()
+            .filter(|block| range.contains(block))
+            .take(self.reprobe_attempts as usize);
+
+        for block in neighbours {
+            print!(
+                "      {} Re-probing block {}... ",
+                "↻".bright_cyan(),
+                block.to_string().bright_white()
+            );
+
+            let neighbour = check_divergence_at_block(
+                poi_client.clone(),
+                indexers,
+                deployment,
+                block,
+                reference,
+                self.max_retries,
+            )
+            .await?;
+            self.print_block_check(&neighbour);
+
+            if neighbour.is_conclusive(self.max_unavailable) {
+                return Ok(neighbour);
+            }
+        }
+
+        Ok(check)
+    }
+
+    fn print_block_check(&self, check: &BlockCheck) {
+        let unavailable = check.unavailable();
+        let unavailable_note = if unavailable.is_empty() {
+            String::new()
+        } else {
+            format!(" ({} unavailable)", unavailable.len())
+                .yellow()
+                .to_string()
+        };
+
+        if let ReferencePoi::Conflict(groups) = &check.reference {
+            println!(
+                "{} Trusted indexers disagree ({} different POIs)",
+                "⚠".yellow().bold(),
+                groups.len().to_string().yellow()
+            );
+            return;
+        }
+
+        if check.has_divergence() {
+            println!(
+                "{} Divergence found ({} indexers){}",
+                "✗".red(),
+                check.diverged().len().to_string().red(),
+                unavailable_note
+            );
+        } else if check.is_conclusive(self.max_unavailable) {
+            println!("{} All POIs match{}", "✓".green(), unavailable_note);
+        } else {
+            println!(
+                "{} Inconclusive ({}/{} indexers unavailable)",
+                "?".yellow(),
+                unavailable.len().to_string().yellow(),
+                check.statuses.len()
+            );
+        }
+
+        let mut reasons: BTreeMap<String, (bool, Vec<&str>)> = BTreeMap::new();
+        for (id, status) in &check.statuses {
+            if let IndexerStatus::Unavailable(reason) = status {
+                reasons
+                    .entry(reason.to_string())
+                    .or_insert_with(|| (reason.is_deterministic(), Vec::new()))
+                    .1
+                    .push(id);
+            }
+        }
+        for (reason, (deterministic, ids)) in reasons {
+            // Deterministic gaps (e.g. block not indexed) will not go away on retry
+            let icon = if deterministic {
+                "–".bright_black()
             } else {
-                println!("{} All POIs match", "✓".green());
-                left = mid + 1;
+                "?".yellow()
+            };
+            println!(
+                "      {} {}: {}",
+                icon,
+                reason.bright_black(),
+                ids.join(", ").bright_black()
+            );
+        }
+
+        for indexer in self.indexer.iter().flatten() {
+            if check.statuses.get(indexer) == Some(&IndexerStatus::Diverged) {
+                let note = match self.reference {
+                    ReferenceMode::Consensus => {
+                        format!("Reference indexer {} is in the minority", indexer)
+                    }
+                    ReferenceMode::Indexer => format!("Trusted indexer {} was outvoted", indexer),
+                };
+                println!("      {} {}", "⚠".yellow().bold(), note.yellow());
             }
         }
+    }
+
+    async fn display_reference_conflict(
+        &self,
+        deployment: &str,
+        poi_client: POIClient,
+        indexers: &BTreeMap<String, Indexer>,
+        reference: &Reference,
+        block: u32,
+    ) -> Result<()> {
+        println!();
+        display_error(&format!(
+            "Trusted indexers disagree on the reference POI at block {}",
+            block
+        ));
+        display_warning("Divergence cannot be determined until the trusted indexers agree");
 
-        Ok(diverged_block)
+        println!(
+            "\n{}",
+            "Fetching POIs at conflicting block...".bright_cyan()
+        );
+        self.display_pois_at_block(deployment, poi_client, indexers, reference, block)
+            .await
     }
 
     async fn display_pois_at_block(
         &self,
+        deployment: &str,
         poi_client: POIClient,
         indexers: &BTreeMap<String, Indexer>,
+        reference: &Reference,
         block: u32,
     ) -> Result<()> {
         let mut pois = Vec::new();
         let mut failed_indexers = Vec::new();
+        let mut not_indexed = Vec::new();
 
-        let mut tasks = JoinSet::new();
-        let poi_client = Arc::new(poi_client);
-
-        for (indexer_id, indexer) in indexers {
-            let id = indexer_id.clone();
-            let url = indexer.url.clone();
-            let deployment = self.deployment.clone();
-            let max_retries = self.max_retries;
-            let poi_client = Arc::clone(&poi_client);
-
-            tasks.spawn(async move {
-                let poi_result = poi_client
-                    .fetch_poi_with_retry(&url, &deployment, block, max_retries)
-                    .await;
-                (id, poi_result)
-            });
-        }
-
-        while let Some(result) = tasks.join_next().await {
-            match result {
-                Ok((indexer_id, poi_result)) => match poi_result {
-                    Ok(poi) => {
-                        pois.push((indexer_id, poi));
-                    }
-                    Err(e) => {
-                        failed_indexers.push((indexer_id, e.to_string()));
-                    }
-                },
+        let results =
+            fetch_pois_from_indexers(poi_client, indexers, deployment, &[block], self.max_retries)
+                .await;
+
+        let block_pois: BTreeMap<&String, &Result<String, PoiError>> =
+            results.iter().map(|(id, pois)| (id, &pois[0])).collect();
+        let reference_poi = resolve_reference_poi(reference, indexers, &block_pois);
+
+        for (indexer_id, poi_result) in block_pois {
+            match poi_result {
+                Ok(poi) => {
+                    pois.push((indexer_id.clone(), poi.clone()));
+                }
+                Err(PoiError::NotIndexed { .. }) => {
+                    not_indexed.push(indexer_id.as_str());
+                }
                 Err(e) => {
-                    failed_indexers.push(("unknown".to_string(), e.to_string()));
+                    failed_indexers.push((indexer_id.clone(), e.to_string()));
                 }
             }
         }
 
+        if !not_indexed.is_empty() {
+            display_warning(&format!(
+                "{} indexer(s) have not indexed block {}: {}",
+                not_indexed.len(),
+                block,
+                not_indexed.join(", ")
+            ));
+        }
+
         if !failed_indexers.is_empty() {
             display_warning(&format!(
                 "Failed to fetch POI from {} indexer(s)",
@@ -271,9 +934,438 @@ impl CheckDivergenceCommand {
             }
         }
 
-        let poi_groups = group_pois_by_hash(indexers, &pois, &self.indexer);
-        display_poi_groups(poi_groups, block, &self.indexer);
+        match (&reference_poi, reference) {
+            (ReferencePoi::Unavailable(reason), _) => display_warning(&format!(
+                "Could not determine the reference POI at block {}: {}",
+                block, reason
+            )),
+            (ReferencePoi::Conflict(groups), _) => display_warning(&format!(
+                "Trusted indexers report {} different POIs at block {}",
+                groups.len(),
+                block
+            )),
+            (ReferencePoi::Resolved(reference_poi), _) => {
+                for indexer in self.indexer.iter().flatten() {
+                    let indexer_poi = pois
+                        .iter()
+                        .find(|(id, _)| id == indexer)
+                        .map(|(_, poi)| poi);
+                    if indexer_poi.is_some_and(|poi| poi != reference_poi) {
+                        display_warning(&match reference {
+                            Reference::Consensus { .. } => format!(
+                                "Reference indexer {} is in the minority at block {}",
+                                indexer, block
+                            ),
+                            Reference::Trusted { .. } => format!(
+                                "Trusted indexer {} was outvoted at block {}",
+                                indexer, block
+                            ),
+                        });
+                    }
+                }
+            }
+        }
+
+        let poi_groups = group_pois_by_hash(indexers, &pois, reference_poi.poi());
+        display_poi_groups(poi_groups, indexers, block, reference);
 
         Ok(())
     }
 }
+
+/// Picks `fanout` evenly spaced blocks inside `[left, right]`. A fanout of one
+/// yields the midpoint, i.e. a plain binary search.
+fn probe_blocks(left: u32, right: u32, fanout: u32) -> Vec<u32> {
+    let span = (right - left) as u64;
+    let parts = fanout as u64 + 1;
+
+    let mut probes: Vec<u32> = (1..parts)
+        .map(|i| left + (span * i / parts) as u32)
+        .collect();
+    probes.dedup();
+    probes
+}
+
+/// Picks the blocks of the next per-indexer round: the uncached probes of each
+/// active search, in order, while they fit in [`MAX_ROUND_PROBES`]. The first
+/// search always fits, so a round holds at least `fanout` blocks. Returns the
+/// probes and the indexers whose searches they were picked for.
+fn schedule_round<'a>(
+    searches: &BTreeMap<&'a String, IndexerSearch>,
+    cache: &BTreeMap<u32, BlockCheck>,
+    fanout: u32,
+) -> (Vec<u32>, BTreeSet<&'a String>) {
+    let mut probes = BTreeSet::new();
+    let mut scheduled = BTreeSet::new();
+
+    for (id, search) in searches {
+        if !search.is_active() {
+            continue;
+        }
+        let wanted: Vec<u32> = probe_blocks(search.left, search.right, fanout)
+            .into_iter()
+            .map(|block| (block + search.failed_rounds).min(search.right))
+            .filter(|block| !cache.contains_key(block) && !probes.contains(block))
+            .collect();
+        if !probes.is_empty() && probes.len() + wanted.len() > MAX_ROUND_PROBES {
+            continue;
+        }
+        probes.extend(wanted);
+        scheduled.insert(*id);
+    }
+
+    (probes.into_iter().collect(), scheduled)
+}
+
+/// Binary search state of a single indexer in per-indexer mode.
+struct IndexerSearch {
+    left: u32,
+    right: u32,
+    last_match: Option<u32>,
+    first_divergence: Option<u32>,
+    failed_rounds: u32,
+    gave_up: bool,
+}
+
+impl IndexerSearch {
+    fn new(start_block: u32, end_block: u32) -> Self {
+        Self {
+            left: start_block,
+            right: end_block,
+            last_match: None,
+            first_divergence: None,
+            failed_rounds: 0,
+            gave_up: false,
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        !self.gave_up && self.left <= self.right
+    }
+
+    /// Narrows the range using every cached block that falls inside it.
+    fn apply(&mut self, indexer_id: &str, cache: &BTreeMap<u32, BlockCheck>) {
+        if !self.is_active() {
+            return;
+        }
+
+        let mut last_match = None;
+        let mut first_divergence = None;
+        for (block, check) in cache.range(self.left..=self.right) {
+            match check.statuses.get(indexer_id) {
+                Some(IndexerStatus::Match) => last_match = Some(*block),
+                Some(IndexerStatus::Diverged) if first_divergence.is_none() => {
+                    first_divergence = Some(*block)
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(block) = last_match {
+            self.last_match = Some(block);
+            self.left = block + 1;
+            self.failed_rounds = 0;
+        }
+        if let Some(block) = first_divergence {
+            self.first_divergence = Some(block);
+            self.failed_rounds = 0;
+            match block.checked_sub(1) {
+                Some(block) => self.right = block,
+                None => self.left = self.right + 1,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::stub::StubServer;
+    use crate::models::AllocationStatus;
+    use clap::Parser;
+    use serde_json::{json, Value};
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        command: CheckDivergenceCommand,
+    }
+
+    fn command(args: &[&str]) -> CheckDivergenceCommand {
+        let deployment = ["poipal", "QmaS7sZNgzqij8FKb7VPK4kppqqsgzujogKDTjTkAcn59j"];
+        Cli::parse_from(deployment.iter().chain(args)).command
+    }
+
+    /// A stub indexer answering `publicProofsOfIndexing` with `poi_at(block)`,
+    /// leaving out the blocks it returns `None` for.
+    fn stub_indexer(poi_at: impl Fn(u32) -> Option<&'static str> + Send + 'static) -> StubServer {
+        StubServer::with_handler(move |_, request| {
+            let request: Value = serde_json::from_str(request).unwrap();
+            let pois: Vec<Value> = request["variables"]["requests"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter_map(|poi_request| {
+                    let block = poi_request["blockNumber"].as_str()?;
+                    let poi = poi_at(block.parse().unwrap())?;
+                    Some(json!({
+                        "deployment": poi_request["deployment"],
+                        "proofOfIndexing": poi,
+                        "block": { "number": block },
+                    }))
+                })
+                .collect();
+            let body = json!({ "data": { "publicProofsOfIndexing": pois } });
+            (200, body.to_string())
+        })
+    }
+
+    /// A stub indexer that matches the reference until `diverged_from`.
+    fn diverging_indexer(diverged_from: Option<u32>) -> StubServer {
+        stub_indexer(move |block| match diverged_from {
+            Some(diverged_from) if block >= diverged_from => Some("0xbad"),
+            _ => Some("0xgood"),
+        })
+    }
+
+    fn indexers(servers: &[(&str, &StubServer)]) -> BTreeMap<String, Indexer> {
+        servers
+            .iter()
+            .map(|(id, server)| {
+                let indexer = Indexer {
+                    id: id.to_string(),
+                    url: server.url.clone(),
+                    staked_tokens: None,
+                    allocation_ids: Vec::new(),
+                    allocation_status: AllocationStatus::Active,
+                };
+                (id.to_string(), indexer)
+            })
+            .collect()
+    }
+
+    fn trusted(id: &str) -> Reference {
+        Reference::Trusted {
+            indexers: vec![id.to_string()],
+            quorum: 1,
+        }
+    }
+
+    /// Blocks requested from `server`, over all of its queries.
+    fn requested_blocks(server: &StubServer) -> Vec<u32> {
+        server
+            .requests()
+            .iter()
+            .flat_map(|request| {
+                let request: Value = serde_json::from_str(request).unwrap();
+                request["variables"]["requests"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|poi_request| {
+                        poi_request["blockNumber"]
+                            .as_str()
+                            .unwrap()
+                            .parse()
+                            .unwrap()
+                    })
+                    .collect::<Vec<u32>>()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn search_narrows_to_the_first_diverged_block() {
+        let reference = diverging_indexer(None);
+        let diverged = diverging_indexer(Some(40));
+        let indexers = indexers(&[("t", &reference), ("a", &diverged)]);
+
+        let outcome = command(&[])
+            .find_diverged_block(
+                "Qm",
+                &POIClient::new().unwrap(),
+                &indexers,
+                &trusted("t"),
+                0,
+                100,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(outcome.diverged_block, Some(40));
+        assert_eq!(outcome.unresolved_range, None);
+        assert_eq!(outcome.reference_conflict, None);
+        assert!(outcome.never_resolved.is_empty());
+        // Narrowing needs both neighbours of the diverged block
+        let probed = requested_blocks(&diverged);
+        assert!(probed.contains(&39) && probed.contains(&40));
+    }
+
+    #[tokio::test]
+    async fn inconclusive_probe_is_recovered_by_reprobing_a_neighbour() {
+        // Both compared indexers skip the first probe, block 50
+        let gap = |diverged_from: u32| {
+            stub_indexer(move |block| match block {
+                50 => None,
+                block if block >= diverged_from => Some("0xbad"),
+                _ => Some("0xgood"),
+            })
+        };
+        let reference = diverging_indexer(None);
+        let (a, b) = (gap(40), gap(101));
+        let indexers = indexers(&[("t", &reference), ("a", &a), ("b", &b)]);
+
+        let outcome = command(&[])
+            .find_diverged_block(
+                "Qm",
+                &POIClient::new().unwrap(),
+                &indexers,
+                &trusted("t"),
+                0,
+                100,
+            )
+            .await
+            .unwrap();
+
+        assert!(requested_blocks(&a).contains(&51));
+        assert_eq!(outcome.diverged_block, Some(40));
+        assert_eq!(outcome.unresolved_range, None);
+    }
+
+    #[tokio::test]
+    async fn unrecoverable_gap_leaves_the_range_unresolved() {
+        let gap = || stub_indexer(|block| (!(45..=55).contains(&block)).then_some("0xgood"));
+        let reference = diverging_indexer(None);
+        let (a, b) = (gap(), gap());
+        let indexers = indexers(&[("t", &reference), ("a", &a), ("b", &b)]);
+
+        let outcome = command(&["--reprobe-attempts", "2"])
+            .find_diverged_block(
+                "Qm",
+                &POIClient::new().unwrap(),
+                &indexers,
+                &trusted("t"),
+                0,
+                100,
+            )
+            .await
+            .unwrap();
+
+        let mut probed = requested_blocks(&a);
+        probed.sort();
+        assert_eq!(probed, vec![49, 50, 51]);
+        assert_eq!(outcome.diverged_block, None);
+        assert_eq!(outcome.unresolved_range, Some((0, 100)));
+        assert_eq!(outcome.never_resolved, vec!["a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn per_indexer_search_finds_each_divergence_and_gives_up_on_failing_indexers() {
+        let reference = diverging_indexer(None);
+        let (a, b) = (diverging_indexer(Some(40)), diverging_indexer(Some(70)));
+        let failing = StubServer::start(404, "");
+        let indexers = indexers(&[("t", &reference), ("a", &a), ("b", &b), ("c", &failing)]);
+
+        let (divergences, reference_conflict) = command(&["--reprobe-attempts", "2"])
+            .find_diverged_blocks_per_indexer(
+                "Qm",
+                &POIClient::new().unwrap(),
+                &indexers,
+                &trusted("t"),
+                0,
+                100,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(reference_conflict, None);
+        let found: Vec<_> = divergences
+            .iter()
+            .map(|d| {
+                (
+                    d.indexer_id.as_str(),
+                    d.last_match,
+                    d.first_divergence,
+                    d.resolved,
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("a", Some(39), Some(40), true),
+                ("b", Some(69), Some(70), true),
+                ("c", None, None, false),
+            ]
+        );
+
+        // Probes are cached across indexers, so no block is fetched twice
+        let mut probed = requested_blocks(&reference);
+        let count = probed.len();
+        probed.sort();
+        probed.dedup();
+        assert_eq!(probed.len(), count);
+    }
+
+    #[test]
+    fn rounds_are_capped_and_leave_later_searches_waiting() {
+        let ids: Vec<String> = ["a", "b", "c"].iter().map(|id| id.to_string()).collect();
+        let searches: BTreeMap<&String, IndexerSearch> = ids
+            .iter()
+            .zip([(0, 999), (1_000, 1_999), (2_000, 2_999)])
+            .map(|(id, (left, right))| (id, IndexerSearch::new(left, right)))
+            .collect();
+
+        let (probes, scheduled) = schedule_round(&searches, &BTreeMap::new(), 20);
+        assert_eq!(probes.len(), 40);
+        assert!(probes.iter().all(|block| *block < 2_000));
+        assert_eq!(scheduled.into_iter().collect::<Vec<_>>(), vec!["a", "b"]);
+
+        // A fanout above the cap still probes the first search
+        let (probes, scheduled) = schedule_round(&searches, &BTreeMap::new(), 80);
+        assert_eq!(probes.len(), 80);
+        assert_eq!(scheduled.into_iter().collect::<Vec<_>>(), vec!["a"]);
+    }
+
+    #[test]
+    fn probes_split_the_range_evenly() {
+        assert_eq!(probe_blocks(0, 100, 1), vec![50]);
+        assert_eq!(probe_blocks(0, 100, 3), vec![25, 50, 75]);
+        assert_eq!(probe_blocks(10, 20, 4), vec![12, 14, 16, 18]);
+    }
+
+    #[test]
+    fn single_block_range_probes_that_block() {
+        assert_eq!(probe_blocks(7, 7, 1), vec![7]);
+        assert_eq!(probe_blocks(7, 7, 8), vec![7]);
+    }
+
+    #[test]
+    fn fanout_larger_than_range_does_not_repeat_blocks() {
+        assert_eq!(probe_blocks(10, 12, 5), vec![10, 11]);
+        assert_eq!(probe_blocks(10, 11, 16), vec![10]);
+    }
+
+    #[test]
+    fn probes_stay_in_range_and_ascend() {
+        for (left, right) in [(0, 1), (5, 9), (100, 1_000_003), (0, u32::MAX)] {
+            for fanout in [1, 2, 7, 64] {
+                let probes = probe_blocks(left, right, fanout);
+                assert!(!probes.is_empty());
+                assert!(probes.len() <= fanout as usize);
+                assert!(probes.windows(2).all(|pair| pair[0] < pair[1]));
+                assert!(probes.iter().all(|block| (left..=right).contains(block)));
+            }
+        }
+    }
+
+    #[test]
+    fn max_unavailable_must_be_a_share() {
+        assert_eq!(parse_share("0"), Ok(0.0));
+        assert_eq!(parse_share("0.5"), Ok(0.5));
+        assert_eq!(parse_share("1"), Ok(1.0));
+        for value in ["-0.1", "1.5", "NaN", "inf", "half"] {
+            assert!(parse_share(value).is_err(), "{} accepted", value);
+        }
+    }
+}
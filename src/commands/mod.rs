@@ -1,5 +1,14 @@
+pub mod args;
+pub mod closing_pois;
+pub mod convert;
+pub mod dispute;
 pub mod divergence;
+pub mod networks;
 pub mod poi;
 
+pub use closing_pois::ClosingPoisCommand;
+pub use convert::ConvertCommand;
+pub use dispute::DisputeBundleCommand;
 pub use divergence::CheckDivergenceCommand;
+pub use networks::NetworksCommand;
 pub use poi::PoiCommand;
@@ -0,0 +1,28 @@
+use anyhow::Result;
+use clap::Args;
+
+use crate::commands::args::RegistryArgs;
+use crate::utils::{display_header, display_info, display_networks};
+
+#[derive(Debug, Args)]
+pub struct NetworksCommand {
+    #[arg(help = "Only list networks whose ID, alias, name or CAIP-2 ID contains this")]
+    query: Option<String>,
+
+    #[command(flatten)]
+    registry: RegistryArgs,
+}
+
+impl NetworksCommand {
+    pub async fn execute(self) -> Result<()> {
+        display_header("Networks Registry");
+
+        let registry = self.registry.registry_client().await?;
+        display_info("Source", &registry.source().to_string());
+        display_info("Version", registry.version());
+
+        display_networks(&registry.search(self.query.as_deref()));
+
+        Ok(())
+    }
+}
@@ -1,36 +1,53 @@
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use clap::Args;
 use colored::Colorize;
-use std::sync::Arc;
-use tokio::task::JoinSet;
-
-use crate::client::eth::EthClient;
-use crate::client::ipfs::IpfsClient;
-use crate::client::registry::RegistryClient;
-use crate::client::{poi::POIClient, subgraph::GraphClient};
+use std::collections::BTreeMap;
+
+use crate::client::epoch::EpochSelector;
+use crate::client::fetch_pois_from_indexers;
+use crate::client::poi::{POIClient, PoiError};
+use crate::commands::args::{
+    ChainHeadArgs, DeploymentArgs, IpfsArgs, NetworkSubgraphArgs, RegistryArgs,
+};
 use crate::models::IndexerPOI;
-use crate::utils::{display_error, display_header, display_info, display_pois, display_success};
+use crate::utils::{
+    display_error, display_header, display_indexers_found, display_info, display_pois,
+    display_success,
+};
 
 #[derive(Debug, Args)]
 pub struct PoiCommand {
-    #[arg(help = "Deployment ID (IPFS hash)")]
-    deployment: String,
+    #[command(flatten)]
+    target: DeploymentArgs,
+
+    #[arg(
+        long,
+        help = "Block number(s) to fetch POI for (comma-separated)",
+        value_delimiter = ','
+    )]
+    block: Option<Vec<u32>>,
+
+    #[arg(
+        long,
+        help = "Fetch POIs at the first block of this epoch (number or 'current')",
+        conflicts_with = "block"
+    )]
+    epoch: Option<EpochSelector>,
+
+    #[command(flatten)]
+    network_subgraph: NetworkSubgraphArgs,
 
-    #[arg(long, help = "Block number to fetch POI for")]
-    block: Option<u32>,
+    #[command(flatten)]
+    chain_head: ChainHeadArgs,
 
-    #[arg(long, help = "API key for The Graph", env = "GRAPH_API_KEY")]
-    api_key: String,
+    #[command(flatten)]
+    registry: RegistryArgs,
 
     #[arg(long, help = "Max retries for fetching POIs", default_value = "3")]
     max_retries: u32,
 
-    #[arg(
-        long,
-        help = "IPFS base URL to fetch subgraph manifest",
-        default_value = "https://ipfs.thegraph.com"
-    )]
-    ipfs_url: String,
+    #[command(flatten)]
+    ipfs: IpfsArgs,
 
     #[arg(
         long,
@@ -43,58 +60,75 @@ pub struct PoiCommand {
 impl PoiCommand {
     pub async fn execute(self) -> Result<()> {
         display_header("Proof of Indexing (POI) Fetcher");
-        display_info("Deployment", &self.deployment);
+        let deployment = self.target.resolve(&self.network_subgraph).await?;
+
+        // Fetched first, as their chain heads back up the RPC lookup
+        println!("\n{}", "Fetching active indexers...".bright_cyan());
+
+        let graph_client = self.network_subgraph.graph_client()?;
+        let mut indexers = graph_client
+            .fetch_indexers(&deployment, self.network_subgraph.include_closed_since)
+            .await?;
+
+        if indexers.is_empty() {
+            display_error("No active indexers found for this deployment");
+            return Ok(());
+        }
+
+        display_indexers_found(&indexers);
 
         // Fetch block if not provided
-        let block = match self.block {
-            Some(b) => b,
+        let blocks = match self.block.clone() {
+            Some(mut blocks) => {
+                blocks.sort_unstable();
+                blocks.dedup();
+                blocks
+            }
             None => {
-                println!(
-                    "\n{}",
-                    "Block not provided. Fetching chain head block...".bright_cyan()
-                );
+                match self.epoch {
+                    Some(_) => println!("\n{}", "Resolving epoch start block...".bright_cyan()),
+                    None => println!(
+                        "\n{}",
+                        "Block not provided. Fetching chain head block...".bright_cyan()
+                    ),
+                }
 
                 // Fetch manifest from IPFS
                 println!("{}", "Fetching manifest from IPFS...".bright_cyan());
-                let ipfs_client = IpfsClient::new(self.ipfs_url.clone())?;
-                let manifest = ipfs_client.fetch_manifest(&self.deployment).await?;
+                let ipfs_client = self.ipfs.ipfs_client()?;
+                let manifest = ipfs_client.fetch_manifest(deployment.ipfs_hash()).await?;
 
                 // Get network from manifest
                 println!("{}", "Fetching network from manifest...".bright_cyan());
-                let network = ipfs_client
-                    .get_network(&manifest)
-                    .await?
-                    .ok_or_else(|| anyhow!("Network not found in manifest"))?;
+                let network = ipfs_client.resolve_network(&manifest).await?;
                 display_info("Network", &network);
 
-                // Get RPC URL from registry
-                println!("{}", "Fetching RPC URL from registry...".bright_cyan());
-                let registry_client = RegistryClient::new().await?;
-                let rpc_url = registry_client.get_public_rpc_url(&network).await?;
-                display_info("RPC URL", &rpc_url);
-
-                // Fetch chain head block
-                println!("{}", "Fetching chain head block...".bright_cyan());
-                let eth_client = EthClient::new(rpc_url)?;
-                let head_block = eth_client.get_chain_head_block_number().await?;
-                display_success(&format!("Using chain head block: {}", head_block));
-                head_block - 15
+                let block = match self.epoch {
+                    Some(epoch) => {
+                        self.network_subgraph
+                            .epoch_start_block(epoch, &network, &self.registry)
+                            .await?
+                    }
+                    None => {
+                        println!("{}", "Fetching chain head block...".bright_cyan());
+                        let head_block = self
+                            .chain_head
+                            .chain_head(&network, &deployment, &indexers, &self.registry)
+                            .await?;
+                        display_success(&format!("Using chain head block: {}", head_block));
+                        head_block - 15
+                    }
+                };
+                vec![block]
             }
         };
 
-        display_info("Block", &block.to_string());
-
-        println!("\n{}", "Fetching active indexers...".bright_cyan());
-
-        let graph_client = GraphClient::new(self.api_key)?;
-        let mut indexers = graph_client.fetch_indexers(&self.deployment).await?;
-
-        if indexers.is_empty() {
-            display_error("No active indexers found for this deployment");
-            return Ok(());
-        }
-
-        display_success(&format!("Found {} active indexers", indexers.len()));
+        let block_list = blocks
+            .iter()
+            .map(|block| block.to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        display_info("Block", &block_list);
 
         // Filter to only include specified indexers
         if let Some(ref include_list) = self.only_indexers {
@@ -114,52 +148,59 @@ impl PoiCommand {
 
         println!("\n{}", "Fetching POIs from indexers...".bright_cyan());
 
-        let poi_client = Arc::new(POIClient::new()?);
-        let mut pois = Vec::new();
+        let poi_client = POIClient::new()?;
+        let mut pois: BTreeMap<u32, Vec<IndexerPOI>> = BTreeMap::new();
         let mut failed_count = 0;
 
-        let mut tasks = JoinSet::new();
-
-        for (indexer_id, indexer) in indexers.iter() {
-            let id = indexer_id.clone();
-            let url = indexer.url.clone();
-            let deployment = self.deployment.clone();
-            let block_num = block;
-            let poi_client = Arc::clone(&poi_client);
-            let max_retries = self.max_retries;
-
-            tasks.spawn(async move {
-                let poi_result = poi_client
-                    .fetch_poi_with_retry(&url, &deployment, block_num, max_retries)
-                    .await;
-                (id, url, poi_result)
-            });
-        }
-
-        while let Some(result) = tasks.join_next().await {
-            match result {
-                Ok((indexer_id, indexer_url, poi_result)) => {
-                    print!("  {} {:<50} ", "→".bright_cyan(), indexer_id);
-
-                    match poi_result {
-                        Ok(poi) => {
-                            println!("{}", "✓".green());
-                            pois.push(IndexerPOI {
-                                indexer_id,
-                                indexer_url,
-                                poi,
-                            });
-                        }
-                        Err(e) => {
-                            println!("{} ({})", "✗".red(), e.to_string().bright_black());
-                            failed_count += 1;
-                        }
+        let results = fetch_pois_from_indexers(
+            poi_client,
+            &indexers,
+            deployment.ipfs_hash(),
+            &blocks,
+            self.max_retries,
+        )
+        .await;
+
+        for (indexer_id, block_pois) in results {
+            print!("  {} {:<50} ", "→".bright_cyan(), indexer_id);
+
+            let indexer_url = indexers[&indexer_id].url.clone();
+            let allocation_status = indexers[&indexer_id].allocation_status;
+            let mut errors = Vec::new();
+
+            for (block, poi_result) in blocks.iter().zip(block_pois) {
+                match poi_result {
+                    Ok(poi) => {
+                        pois.entry(*block).or_default().push(IndexerPOI {
+                            indexer_id: indexer_id.clone(),
+                            indexer_url: indexer_url.clone(),
+                            allocation_status,
+                            poi,
+                        });
                     }
+                    Err(e) => errors.push(e),
                 }
-                Err(e) => {
+            }
+
+            // Deterministic errors (e.g. block not indexed) are shown apart from fetch failures
+            let icon = if errors.iter().all(PoiError::is_deterministic) {
+                "–".yellow()
+            } else {
+                "✗".red()
+            };
+
+            match errors.first() {
+                None => println!("{}", "✓".green()),
+                Some(e) if blocks.len() == 1 => {
+                    println!("{} ({})", icon, e.to_string().bright_black());
+                    failed_count += 1;
+                }
+                Some(e) => {
                     println!(
-                        "  {} Task failed: {}",
-                        "✗".red(),
+                        "{} ({}/{} blocks failed: {})",
+                        icon,
+                        errors.len(),
+                        blocks.len(),
                         e.to_string().bright_black()
                     );
                     failed_count += 1;
@@ -174,7 +215,13 @@ impl PoiCommand {
             );
         }
 
-        display_pois(pois, block, &self.deployment);
+        for block in blocks {
+            display_pois(
+                pois.remove(&block).unwrap_or_default(),
+                block,
+                deployment.ipfs_hash(),
+            );
+        }
 
         Ok(())
     }
@@ -2,7 +2,10 @@ use anyhow::Result;
 use clap::{Parser, Subcommand};
 use colored::Colorize;
 use poipal::client::update::UpdateChecker;
-use poipal::commands::{CheckDivergenceCommand, PoiCommand};
+use poipal::commands::{
+    CheckDivergenceCommand, ClosingPoisCommand, ConvertCommand, DisputeBundleCommand,
+    NetworksCommand, PoiCommand,
+};
 
 #[derive(Debug, Parser)]
 #[command(
@@ -36,6 +39,44 @@ enum Commands {
                       where indexing discrepancies begin."
     )]
     CheckDivergence(CheckDivergenceCommand),
+
+    #[command(
+        name = "closing-pois",
+        about = "Look up public POIs at the closing blocks of closed allocations",
+        long_about = "Lists closed allocations for a deployment and fetches public POIs at the \
+                      start block of each closing epoch, showing whether the allocation's \
+                      indexer served the consensus public POI there. The POI submitted on \
+                      chain is shown but not judged: graph-node derives it with the indexer's \
+                      address, while public POIs use the zero address, so the two never match."
+    )]
+    ClosingPois(ClosingPoisCommand),
+
+    #[command(
+        name = "dispute-bundle",
+        about = "Write a JSON evidence bundle for disputing a diverged indexer",
+        long_about = "Gathers the disputed allocation, its closing epoch and submitted POI, the \
+                      consensus and indexer POIs around the diverged block, and every query \
+                      and raw response used, into a self-contained JSON document."
+    )]
+    DisputeBundle(DisputeBundleCommand),
+
+    #[command(
+        name = "convert",
+        about = "Convert a deployment ID between its IPFS hash and bytes32 forms",
+        long_about = "Validates a deployment ID given either as a Qm... IPFS hash or as the \
+                      0x-prefixed bytes32 hex used by contracts and the network subgraph, and \
+                      prints both forms."
+    )]
+    Convert(ConvertCommand),
+
+    #[command(
+        name = "networks",
+        about = "List and search the networks registry",
+        long_about = "Lists the networks in The Graph's networks registry that poipal uses to \
+                      find RPC URLs and chain IDs, optionally filtered by a search term. The \
+                      registry is cached on disk, and a bundled snapshot is used when offline."
+    )]
+    Networks(NetworksCommand),
 }
 
 #[tokio::main]
@@ -58,6 +99,30 @@ async fn main() -> Result<()> {
                 std::process::exit(1);
             }
         }
+        Commands::ClosingPois(cmd) => {
+            if let Err(e) = cmd.execute().await {
+                eprintln!("{} {}", "Error:".red().bold(), e);
+                std::process::exit(1);
+            }
+        }
+        Commands::DisputeBundle(cmd) => {
+            if let Err(e) = cmd.execute().await {
+                eprintln!("{} {}", "Error:".red().bold(), e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Convert(cmd) => {
+            if let Err(e) = cmd.execute().await {
+                eprintln!("{} {}", "Error:".red().bold(), e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Networks(cmd) => {
+            if let Err(e) = cmd.execute().await {
+                eprintln!("{} {}", "Error:".red().bold(), e);
+                std::process::exit(1);
+            }
+        }
     }
 
     Ok(())
@@ -0,0 +1,186 @@
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::str::FromStr;
+
+/// Multihash prefix of a deployment's IPFS hash: sha2-256 with a 32 byte digest
+const SHA2_256_PREFIX: [u8; 2] = [0x12, 0x20];
+
+/// A subgraph deployment ID. Parses from either the `Qm...` IPFS hash or the
+/// `0x`-prefixed bytes32 form used by contracts and the network subgraph,
+/// and displays as the IPFS hash.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DeploymentId {
+    bytes: [u8; 32],
+    ipfs_hash: String,
+}
+
+impl DeploymentId {
+    fn new(bytes: [u8; 32]) -> Self {
+        let mut multihash = SHA2_256_PREFIX.to_vec();
+        multihash.extend_from_slice(&bytes);
+        let ipfs_hash = bs58::encode(multihash).into_string();
+        Self { bytes, ipfs_hash }
+    }
+
+    /// The `Qm...` form, as used by indexers and IPFS
+    pub fn ipfs_hash(&self) -> &str {
+        &self.ipfs_hash
+    }
+
+    /// The bytes32 form, e.g. `0x6e8e...`
+    pub fn to_hex(&self) -> String {
+        let hex: String = self
+            .bytes
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        format!("0x{}", hex)
+    }
+
+    fn from_ipfs_hash(hash: &str) -> Option<Self> {
+        if hash.len() != 46 || !hash.starts_with("Qm") {
+            return None;
+        }
+        let multihash = bs58::decode(hash).into_vec().ok()?;
+        let digest = multihash.strip_prefix(&SHA2_256_PREFIX)?;
+        digest.try_into().ok().map(Self::new)
+    }
+
+    fn from_hex(hex: &str) -> Option<Self> {
+        let hex = hex.strip_prefix("0x")?;
+        // `from_str_radix` would also accept a sign, e.g. `+f`
+        if hex.len() != 64 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        let mut bytes = [0u8; 32];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+        }
+        Some(Self::new(bytes))
+    }
+}
+
+impl FromStr for DeploymentId {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let id = if s.starts_with("0x") {
+            Self::from_hex(s)
+        } else {
+            Self::from_ipfs_hash(s)
+        };
+        id.ok_or_else(|| {
+            anyhow!(
+                "Invalid deployment ID '{}': expected an IPFS hash like Qm... (46 base58 characters) \
+                 or a 0x-prefixed bytes32 hex string",
+                s
+            )
+        })
+    }
+}
+
+impl fmt::Display for DeploymentId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.ipfs_hash)
+    }
+}
+
+/// A subgraph ID as shown on Explorer: 32 bytes, base58 encoded. Unlike a
+/// deployment ID it stays the same across published versions.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SubgraphId(String);
+
+impl FromStr for SubgraphId {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        match bs58::decode(s).into_vec() {
+            Ok(bytes) if bytes.len() == 32 => Ok(Self(s.to_string())),
+            _ => Err(anyhow!(
+                "Invalid subgraph ID '{}': expected 32 base58 encoded bytes, as shown on Explorer",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for SubgraphId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A command's target: a deployment, or a subgraph to resolve to one.
+#[derive(Debug, Clone)]
+pub enum DeploymentRef {
+    Deployment(DeploymentId),
+    Subgraph(SubgraphId),
+}
+
+impl FromStr for DeploymentRef {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Ok(subgraph) = s.parse() {
+            return Ok(Self::Subgraph(subgraph));
+        }
+        s.parse().map(Self::Deployment).map_err(|_| {
+            anyhow!(
+                "Invalid deployment ID '{}': expected an IPFS hash like Qm..., a 0x-prefixed \
+                 bytes32 hex string or a subgraph ID",
+                s.trim()
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IPFS_HASH: &str = "QmaS7sZNgzqij8FKb7VPK4kppqqsgzujogKDTjTkAcn59j";
+    const HEX: &str = "0xb3b2dc453b9dcc99c3cd25da9f79ea6cff92642b7695567dbb6bca2dfad57722";
+
+    #[test]
+    fn converts_between_ipfs_hash_and_hex() {
+        let id: DeploymentId = IPFS_HASH.parse().unwrap();
+        assert_eq!(id.to_hex(), HEX);
+        assert_eq!(id.ipfs_hash(), IPFS_HASH);
+
+        let id: DeploymentId = HEX.parse().unwrap();
+        assert_eq!(id.ipfs_hash(), IPFS_HASH);
+        assert_eq!(id.to_hex(), HEX);
+
+        let upper = format!("0x{}", HEX[2..].to_uppercase());
+        assert_eq!(upper.parse::<DeploymentId>().unwrap(), id);
+    }
+
+    #[test]
+    fn rejects_other_multihashes() {
+        // 46 characters and a Qm prefix, but a 0x121f multihash prefix
+        assert!("QmNLei78zWmzUdbeRB3CiUfAizWUrbeeZh5K1rhAQKCh4z"
+            .parse::<DeploymentId>()
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_wrong_lengths() {
+        assert!(IPFS_HASH[..45].parse::<DeploymentId>().is_err());
+        assert!(format!("{}1", IPFS_HASH).parse::<DeploymentId>().is_err());
+        assert!(HEX[..65].parse::<DeploymentId>().is_err());
+        assert!(format!("{}00", HEX).parse::<DeploymentId>().is_err());
+    }
+
+    #[test]
+    fn rejects_bad_hex() {
+        let signed = format!("0x{}", "+f".repeat(32));
+        assert!(signed.parse::<DeploymentId>().is_err());
+        let not_hex = format!("0x{}g", &HEX[2..65]);
+        assert!(not_hex.parse::<DeploymentId>().is_err());
+        let not_ascii = format!("0x{}é", &HEX[2..64]);
+        assert!(not_ascii.parse::<DeploymentId>().is_err());
+        assert!(HEX[2..].parse::<DeploymentId>().is_err());
+    }
+}
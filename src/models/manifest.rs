@@ -0,0 +1,192 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::collections::BTreeSet;
+
+/// The parts of a subgraph manifest (`subgraph.yaml`) poipal relies on.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubgraphManifest {
+    pub spec_version: String,
+    #[serde(default)]
+    pub features: Vec<String>,
+    #[serde(default)]
+    pub data_sources: Vec<DataSource>,
+    #[serde(default)]
+    pub templates: Vec<DataSource>,
+    pub graft: Option<Graft>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DataSource {
+    pub kind: String,
+    pub name: Option<String>,
+    pub network: Option<String>,
+    pub source: Option<Source>,
+}
+
+impl DataSource {
+    /// A composed subgraph data source, consuming the deployment in `source.address`.
+    pub fn is_subgraph(&self) -> bool {
+        self.kind == "subgraph"
+    }
+
+    pub fn is_substreams(&self) -> bool {
+        self.kind == "substreams"
+    }
+
+    pub fn start_block(&self) -> Option<u32> {
+        self.source.as_ref()?.start_block
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Source {
+    /// Contract address, or the source deployment ID for `kind: subgraph`
+    pub address: Option<String>,
+    pub start_block: Option<u32>,
+    pub end_block: Option<u32>,
+    /// Substreams package, for `kind: substreams`
+    pub package: Option<SubstreamsPackage>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubstreamsPackage {
+    pub module_name: String,
+    pub file: IpfsLink,
+}
+
+/// An IPFS link as written into deployed manifests: `{ /: /ipfs/<hash> }`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct IpfsLink {
+    #[serde(rename = "/")]
+    pub link: String,
+}
+
+impl IpfsLink {
+    pub fn hash(&self) -> &str {
+        self.link.trim_start_matches("/ipfs/")
+    }
+}
+
+/// The deployment this subgraph was grafted onto, and the block it was
+/// grafted at.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Graft {
+    pub base: String,
+    pub block: u32,
+}
+
+/// A deployment and its manifest, as one link of a graft chain.
+#[derive(Debug, Clone)]
+pub struct LineageEntry {
+    pub deployment: String,
+    pub manifest: SubgraphManifest,
+}
+
+impl SubgraphManifest {
+    pub fn from_yaml(yaml: &str) -> Result<Self> {
+        serde_norway::from_str(yaml).map_err(|e| anyhow!("Invalid subgraph manifest: {}", e))
+    }
+
+    /// Every network named by a data source or template.
+    pub fn networks(&self) -> BTreeSet<&str> {
+        self.data_sources
+            .iter()
+            .chain(&self.templates)
+            .filter_map(|data_source| data_source.network.as_deref())
+            .collect()
+    }
+}
+
+/// Returns the only network in `networks`, failing when there is none or
+/// more than one.
+pub fn single_network<S: AsRef<str>>(networks: BTreeSet<S>) -> Result<S> {
+    if networks.len() > 1 {
+        return Err(anyhow!(
+            "Manifest uses more than one network ({}); multi-network subgraphs are not supported",
+            networks
+                .iter()
+                .map(|network| network.as_ref())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    networks
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("Network not found in manifest"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"
+specVersion: 1.0.0
+schema:
+  file:
+    /: /ipfs/QmSchema
+graft:
+  base: QmaS7sZNgzqij8FKb7VPK4kppqqsgzujogKDTjTkAcn59j
+  block: 500
+dataSources:
+  - kind: ethereum/contract
+    name: Token
+    network: mainnet
+    source:
+      address: "0x0000000000000000000000000000000000000001"
+      startBlock: 200
+      endBlock: 900
+  - kind: substreams
+    network: mainnet
+    source:
+      package:
+        moduleName: map_x
+        file:
+          /: /ipfs/QmPackage
+templates:
+  - kind: ethereum/contract
+    name: Pair
+    network: mainnet
+"#;
+
+    #[test]
+    fn parses_the_fields_poipal_uses() {
+        let manifest = SubgraphManifest::from_yaml(MANIFEST).unwrap();
+        assert_eq!(manifest.spec_version, "1.0.0");
+        assert_eq!(manifest.networks(), BTreeSet::from(["mainnet"]));
+        assert_eq!(manifest.data_sources[0].start_block(), Some(200));
+        let source = manifest.data_sources[0].source.as_ref().unwrap();
+        assert_eq!(source.end_block, Some(900));
+        assert_eq!(
+            manifest.data_sources[1].source.as_ref().unwrap().end_block,
+            None
+        );
+
+        let graft = manifest.graft.unwrap();
+        assert_eq!(graft.block, 500);
+
+        let package = manifest.data_sources[1].source.as_ref().unwrap();
+        let package = package.package.as_ref().unwrap();
+        assert_eq!(package.module_name, "map_x");
+        assert_eq!(package.file.hash(), "QmPackage");
+    }
+
+    #[test]
+    fn rejects_several_networks() {
+        let manifest = SubgraphManifest::from_yaml(
+            "specVersion: 0.0.4\ndataSources:\n  - kind: ethereum\n    network: mainnet\n  - kind: ethereum\n    network: base\n",
+        )
+        .unwrap();
+        assert!(single_network(manifest.networks()).is_err());
+    }
+
+    #[test]
+    fn rejects_text_that_is_not_a_manifest() {
+        assert!(SubgraphManifest::from_yaml("hello world").is_err());
+        assert!(SubgraphManifest::from_yaml("specVersion: [").is_err());
+    }
+}
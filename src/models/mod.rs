@@ -1,14 +1,75 @@
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::fmt;
 
-#[derive(Debug, Deserialize, Serialize)]
+use crate::client::poi::PoiError;
+
+mod deployment;
+mod manifest;
+mod protobuf;
+mod spkg;
+mod unixfs;
+
+pub use deployment::{DeploymentId, DeploymentRef, SubgraphId};
+pub use manifest::{
+    single_network, DataSource, Graft, IpfsLink, LineageEntry, Source, SubgraphManifest,
+    SubstreamsPackage,
+};
+pub use spkg::module_initial_block;
+pub use unixfs::{decode_file_node, verify_block, UnixfsNode};
+
+/// A query sent to an indexer or subgraph, kept verbatim with its raw
+/// response so results can be re-verified independently.
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordedQuery {
+    pub endpoint: String,
+    pub request: serde_json::Value,
+    pub status: u16,
+    /// The response body; JSON when it parses as such
+    pub response: serde_json::Value,
+}
+
+/// A GraphQL request. Queries are static documents; all user input goes
+/// through `variables`.
+#[derive(Debug, Serialize)]
 pub struct GraphQLQuery {
-    pub query: String,
+    pub query: &'static str,
+    pub variables: serde_json::Value,
 }
 
+/// A standard GraphQL response. `data` may be missing or partial when
+/// `errors` is not empty.
 #[derive(Debug, Deserialize)]
 pub struct GraphQLResponse<T> {
-    pub data: T,
+    pub data: Option<T>,
+    #[serde(default)]
+    pub errors: Vec<GraphQLError>,
+    pub extensions: Option<serde_json::Value>,
+}
+
+impl<T> GraphQLResponse<T> {
+    pub fn error_messages(&self) -> Vec<String> {
+        self.errors
+            .iter()
+            .map(|error| error.message.clone())
+            .collect()
+    }
+
+    /// Returns the data only if the server reported no errors.
+    pub fn into_result(self) -> anyhow::Result<T> {
+        if !self.errors.is_empty() {
+            return Err(anyhow::anyhow!("{}", self.error_messages().join("; ")));
+        }
+        self.data
+            .ok_or_else(|| anyhow::anyhow!("Response contains neither data nor errors"))
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct GraphQLError {
+    pub message: String,
+    pub path: Option<Vec<serde_json::Value>>,
+    pub extensions: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -17,19 +78,93 @@ pub struct AllocationsData {
 }
 
 #[derive(Debug, Deserialize)]
+/// An allocation as returned by either the legacy or the Horizon network
+/// subgraph. `provision` is only queried on Horizon.
 pub struct Allocation {
-    pub indexer: Indexer,
+    pub id: String,
+    pub status: AllocationStatus,
+    /// POI submitted on chain when the allocation was closed
+    pub poi: Option<String>,
+    #[serde(rename = "closedAtEpoch")]
+    pub closed_at_epoch: Option<u64>,
+    pub indexer: NetworkIndexer,
+    #[serde(default)]
+    pub provision: Option<Provision>,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+impl Allocation {
+    /// The indexer's service URL: the SubgraphService registration on Horizon,
+    /// falling back to the legacy service registry URL.
+    pub fn indexer_url(&self) -> Option<&str> {
+        self.provision
+            .as_ref()
+            .and_then(|provision| provision.url.as_deref())
+            .or(self.indexer.url.as_deref())
+            .filter(|url| !url.is_empty())
+    }
+
+    pub fn into_indexer(self) -> Option<Indexer> {
+        let url = self.indexer_url()?.to_string();
+        Some(Indexer {
+            id: self.indexer.id,
+            url,
+            staked_tokens: self.indexer.staked_tokens,
+            allocation_ids: vec![self.id],
+            allocation_status: self.status,
+        })
+    }
+}
+
+/// Allocation status on the network subgraph. Every non-active status
+/// (closed, finalized, claimed) counts as closed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum AllocationStatus {
+    Active,
+    #[serde(other)]
+    Closed,
+}
+
+impl fmt::Display for AllocationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AllocationStatus::Active => write!(f, "active"),
+            AllocationStatus::Closed => write!(f, "closed"),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NetworkIndexer {
+    pub id: String,
+    pub url: Option<String>,
+    #[serde(rename = "stakedTokens", default)]
+    pub staked_tokens: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Provision {
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Clone)]
 pub struct Indexer {
     pub id: String,
     pub url: String,
+    pub staked_tokens: Option<String>,
+    /// Allocations this indexer holds on the queried deployment
+    pub allocation_ids: Vec<String>,
+    /// Active if any of the indexer's allocations is still active
+    pub allocation_status: AllocationStatus,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct POIResponse {
-    pub data: POIData,
+impl Indexer {
+    /// Staked tokens in wei, or zero when unknown.
+    pub fn stake(&self) -> u128 {
+        self.staked_tokens
+            .as_deref()
+            .and_then(|tokens| tokens.parse().ok())
+            .unwrap_or(0)
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -42,7 +177,7 @@ pub struct POIData {
 pub struct POI {
     pub deployment: Option<String>,
     #[serde(rename = "proofOfIndexing")]
-    pub poi: String,
+    pub poi: Option<String>,
     pub block: Option<Block>,
 }
 
@@ -51,14 +186,33 @@ pub struct Block {
     pub number: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct IndexingStatusesData {
+    #[serde(rename = "indexingStatuses")]
+    pub statuses: Vec<IndexingStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IndexingStatus {
+    pub chains: Vec<ChainIndexingStatus>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChainIndexingStatus {
+    pub network: String,
+    #[serde(rename = "chainHeadBlock")]
+    pub chain_head_block: Option<Block>,
+}
+
 #[derive(Debug)]
 pub struct IndexerPOI {
     pub indexer_id: String,
     pub indexer_url: String,
+    pub allocation_status: AllocationStatus,
     pub poi: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct POIGroup {
     pub poi: String,
     pub indexers: BTreeMap<String, String>,
@@ -69,3 +223,214 @@ pub struct POIGroup {
 pub struct EthBlock {
     pub number: String,
 }
+
+/// Outcome of comparing one indexer's POI against the reference POI.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexerStatus {
+    Match,
+    Diverged,
+    Unavailable(UnavailableReason),
+}
+
+/// Why an indexer could not be compared at a block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnavailableReason {
+    /// The indexer's own POI could not be fetched
+    Fetch(PoiError),
+    /// There was no reference POI to compare against
+    NoReference(String),
+}
+
+impl UnavailableReason {
+    pub fn is_deterministic(&self) -> bool {
+        matches!(self, UnavailableReason::Fetch(e) if e.is_deterministic())
+    }
+}
+
+impl fmt::Display for UnavailableReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnavailableReason::Fetch(e) => write!(f, "{}", e),
+            UnavailableReason::NoReference(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+/// What each indexer's POI is compared against.
+#[derive(Debug, Clone)]
+pub enum Reference {
+    /// POI agreed on by at least `quorum` of the trusted indexers
+    Trusted {
+        indexers: Vec<String>,
+        quorum: usize,
+    },
+    /// The POI backed by a majority of the responding indexers
+    Consensus { stake_weighted: bool },
+}
+
+impl Reference {
+    /// Whether `indexer_id` is one of the trusted indexers defining the reference.
+    pub fn is_reference_indexer(&self, indexer_id: &str) -> bool {
+        matches!(self, Reference::Trusted { indexers, .. } if indexers.iter().any(|id| id == indexer_id))
+    }
+
+    /// Whether `indexer_id` is compared against the reference. A sole trusted
+    /// indexer is the reference; several trusted indexers are compared too,
+    /// since a quorum can outvote any of them.
+    pub fn is_compared(&self, indexer_id: &str) -> bool {
+        match self {
+            Reference::Trusted { indexers, .. } if indexers.len() == 1 => indexers[0] != indexer_id,
+            _ => true,
+        }
+    }
+}
+
+/// The reference POI at a single block.
+#[derive(Debug, Clone)]
+pub enum ReferencePoi {
+    Resolved(String),
+    /// No reference could be determined, with the reason why
+    Unavailable(String),
+    /// Trusted indexers reported different POIs and not exactly one of them
+    /// reached quorum, keyed by POI
+    Conflict(BTreeMap<String, Vec<String>>),
+}
+
+impl ReferencePoi {
+    pub fn poi(&self) -> Option<&str> {
+        match self {
+            ReferencePoi::Resolved(poi) => Some(poi),
+            _ => None,
+        }
+    }
+
+    pub fn is_conflict(&self) -> bool {
+        matches!(self, ReferencePoi::Conflict(_))
+    }
+}
+
+#[derive(Debug)]
+pub struct BlockCheck {
+    pub block: u32,
+    pub reference: ReferencePoi,
+    pub statuses: BTreeMap<String, IndexerStatus>,
+}
+
+impl BlockCheck {
+    pub fn diverged(&self) -> Vec<&String> {
+        self.with_status(|status| *status == IndexerStatus::Diverged)
+    }
+
+    pub fn unavailable(&self) -> Vec<&String> {
+        self.with_status(|status| matches!(status, IndexerStatus::Unavailable(_)))
+    }
+
+    pub fn has_divergence(&self) -> bool {
+        self.statuses
+            .values()
+            .any(|status| *status == IndexerStatus::Diverged)
+    }
+
+    /// A block is conclusive when it shows a divergence, or when the share of
+    /// unavailable indexers does not exceed `max_unavailable_ratio`.
+    pub fn is_conclusive(&self, max_unavailable_ratio: f64) -> bool {
+        if self.has_divergence() || self.statuses.is_empty() {
+            return true;
+        }
+        let unavailable = self.unavailable().len() as f64;
+        unavailable / self.statuses.len() as f64 <= max_unavailable_ratio
+    }
+
+    fn with_status(&self, predicate: impl Fn(&IndexerStatus) -> bool) -> Vec<&String> {
+        self.statuses
+            .iter()
+            .filter(|(_, status)| predicate(status))
+            .map(|(id, _)| id)
+            .collect()
+    }
+}
+
+/// Per-indexer result of a divergence search against the reference.
+#[derive(Debug)]
+pub struct IndexerDivergence {
+    pub indexer_id: String,
+    pub last_match: Option<u32>,
+    pub first_divergence: Option<u32>,
+    /// False when the search stopped early because the indexer kept being unavailable
+    pub resolved: bool,
+}
+
+/// How an allocation's indexer compares to consensus at the closing block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClosingPoiStatus {
+    /// The indexer's public POI matches the consensus public POI
+    Matches,
+    /// The indexer's public POI disagrees with consensus
+    Diverged,
+    /// The indexer serves no public POI at the block, with the reason why
+    NotServed(String),
+    /// No comparison was possible, with the reason why
+    Inconclusive(String),
+}
+
+/// Public POIs at the closing block of one closed allocation. The closing POI
+/// is only shown: graph-node derives it with the indexer's address, so it
+/// cannot be compared with public POIs, which use the zero address.
+#[derive(Debug)]
+pub struct ClosingPoiLookup {
+    pub allocation_id: String,
+    pub indexer_id: String,
+    pub closed_at_epoch: Option<u64>,
+    /// Start block of the closing epoch, at which public POIs are fetched
+    pub block: Option<u32>,
+    /// The POI submitted on chain when closing the allocation
+    pub closing_poi: Option<String>,
+    /// The indexer's own public POI at `block`
+    pub public_poi: Option<String>,
+    pub consensus_poi: Option<String>,
+    pub status: ClosingPoiStatus,
+}
+
+/// Self-contained evidence for an indexing dispute against one indexer. The
+/// recorded queries and raw responses let a third party re-verify every value.
+#[derive(Debug, Serialize)]
+pub struct DisputeBundle {
+    /// Unix timestamp (seconds) at which the bundle was generated
+    pub generated_at: u64,
+    pub deployment: String,
+    /// The deployment ID as it appears in dispute and staking contracts
+    pub deployment_bytes32: String,
+    pub indexer: String,
+    /// How the consensus POI was determined
+    pub reference: String,
+    pub diverged_block: u32,
+    pub allocation: Option<DisputedAllocation>,
+    pub blocks: Vec<BlockEvidence>,
+    pub queries: Vec<RecordedQuery>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DisputedAllocation {
+    pub id: String,
+    pub closed_at_epoch: Option<u64>,
+    /// IPFS hash of the manifest `network` was read from
+    pub manifest: Option<String>,
+    /// The deployment's chain, on which `epoch_start_block` lies
+    pub network: Option<String>,
+    /// Start block of the closing epoch on the deployment's chain, for which
+    /// the POI was submitted
+    pub epoch_start_block: Option<u32>,
+    pub submitted_poi: Option<String>,
+}
+
+/// POIs reported by every indexer at one block.
+#[derive(Debug, Serialize)]
+pub struct BlockEvidence {
+    pub block: u32,
+    pub consensus_poi: Option<String>,
+    /// The disputed indexer's public POI
+    pub indexer_poi: Option<String>,
+    /// Why the disputed indexer's POI could not be fetched
+    pub indexer_error: Option<String>,
+    pub groups: Vec<POIGroup>,
+}
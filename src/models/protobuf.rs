@@ -0,0 +1,93 @@
+//! A minimal protobuf wire-format reader, for the few binary formats poipal
+//! inspects without their full schemas.
+
+use anyhow::{anyhow, Result};
+
+pub(crate) enum Field<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+/// Iterates over the top-level `(field number, value)` pairs of a message.
+pub(crate) struct Fields<'a> {
+    data: &'a [u8],
+    failed: bool,
+}
+
+impl<'a> Fields<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            failed: false,
+        }
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = self
+                .data
+                .split_first()
+                .ok_or_else(|| anyhow!("Truncated protobuf message"))?;
+            self.data = rest;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(anyhow!("Invalid varint in protobuf message"))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(anyhow!("Truncated protobuf message"));
+        }
+        let (value, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(value)
+    }
+
+    fn field(&mut self) -> Result<(u64, Field<'a>)> {
+        let key = self.varint()?;
+        let value = match key & 0x7 {
+            0 => Field::Varint(self.varint()?),
+            1 => {
+                self.take(8)?;
+                Field::Fixed
+            }
+            2 => {
+                let len = self.varint()? as usize;
+                Field::Bytes(self.take(len)?)
+            }
+            5 => {
+                self.take(4)?;
+                Field::Fixed
+            }
+            wire_type => {
+                return Err(anyhow!(
+                    "Unsupported wire type {} in protobuf message",
+                    wire_type
+                ))
+            }
+        };
+        Ok((key >> 3, value))
+    }
+}
+
+impl<'a> Iterator for Fields<'a> {
+    type Item = (u64, Result<Field<'a>>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() || self.failed {
+            return None;
+        }
+        match self.field() {
+            Ok((field, value)) => Some((field, Ok(value))),
+            Err(e) => {
+                self.failed = true;
+                Some((0, Err(e)))
+            }
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! Reads module initial blocks from a Substreams package (`.spkg`).
+
+use anyhow::{anyhow, Result};
+
+use super::protobuf::{Field, Fields};
+
+/// `sf.substreams.v1.Package.modules`
+const PACKAGE_MODULES: u64 = 6;
+/// `sf.substreams.v1.Modules.modules`
+const MODULES_MODULES: u64 = 1;
+/// `sf.substreams.v1.Module.name`
+const MODULE_NAME: u64 = 1;
+/// `sf.substreams.v1.Module.initial_block`
+const MODULE_INITIAL_BLOCK: u64 = 8;
+
+/// Returns the initial block of `module_name` in the encoded package.
+pub fn module_initial_block(package: &[u8], module_name: &str) -> Result<u64> {
+    for (field, value) in Fields::new(package) {
+        let Field::Bytes(modules) = value? else {
+            continue;
+        };
+        if field != PACKAGE_MODULES {
+            continue;
+        }
+
+        for (field, value) in Fields::new(modules) {
+            let Field::Bytes(module) = value? else {
+                continue;
+            };
+            if field != MODULES_MODULES {
+                continue;
+            }
+
+            let mut name = None;
+            let mut initial_block = 0;
+            for (field, value) in Fields::new(module) {
+                match (field, value?) {
+                    (MODULE_NAME, Field::Bytes(bytes)) => name = Some(bytes),
+                    (MODULE_INITIAL_BLOCK, Field::Varint(block)) => initial_block = block,
+                    _ => {}
+                }
+            }
+            if name == Some(module_name.as_bytes()) {
+                return Ok(initial_block);
+            }
+        }
+    }
+
+    Err(anyhow!(
+        "Module '{}' not found in substreams package",
+        module_name
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn varint(mut value: u64) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        while value >= 0x80 {
+            bytes.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        bytes.push(value as u8);
+        bytes
+    }
+
+    fn bytes_field(field: u64, data: &[u8]) -> Vec<u8> {
+        let mut bytes = varint(field << 3 | 2);
+        bytes.extend(varint(data.len() as u64));
+        bytes.extend(data);
+        bytes
+    }
+
+    fn module(name: &str, initial_block: Option<u64>) -> Vec<u8> {
+        let mut module = bytes_field(MODULE_NAME, name.as_bytes());
+        if let Some(block) = initial_block {
+            module.extend(varint(MODULE_INITIAL_BLOCK << 3));
+            module.extend(varint(block));
+        }
+        module
+    }
+
+    fn package(modules: &[Vec<u8>]) -> Vec<u8> {
+        let modules: Vec<u8> = modules
+            .iter()
+            .flat_map(|module| bytes_field(MODULES_MODULES, module))
+            .collect();
+        // A leading unrelated field, as real packages start with proto files
+        let mut package = bytes_field(1, b"proto");
+        package.extend(bytes_field(PACKAGE_MODULES, &modules));
+        package
+    }
+
+    #[test]
+    fn finds_the_named_module() {
+        let package = package(&[
+            module("store_x", Some(5)),
+            module("map_x", Some(12_345_678)),
+        ]);
+        assert_eq!(module_initial_block(&package, "map_x").unwrap(), 12_345_678);
+        assert_eq!(module_initial_block(&package, "store_x").unwrap(), 5);
+    }
+
+    #[test]
+    fn initial_block_defaults_to_zero() {
+        let package = package(&[module("map_x", None)]);
+        assert_eq!(module_initial_block(&package, "map_x").unwrap(), 0);
+    }
+
+    #[test]
+    fn missing_module_is_an_error() {
+        let package = package(&[module("map_x", Some(1))]);
+        let error = module_initial_block(&package, "map_y").unwrap_err();
+        assert!(error.to_string().contains("map_y"));
+        assert!(module_initial_block(&[], "map_x").is_err());
+    }
+
+    #[test]
+    fn truncated_package_is_an_error() {
+        let package = package(&[module("map_x", Some(1))]);
+        for len in [package.len() - 1, package.len() / 2, 1] {
+            assert!(
+                module_initial_block(&package[..len], "map_x").is_err(),
+                "truncated to {} bytes",
+                len
+            );
+        }
+    }
+}
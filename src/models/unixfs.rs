@@ -0,0 +1,155 @@
+//! CIDv0 hashes and the dag-pb / UnixFS blocks they address, so content
+//! fetched from IPFS can be checked against the hash it was requested by.
+
+use anyhow::{anyhow, Result};
+use sha2::{Digest, Sha256};
+
+use super::protobuf::{Field, Fields};
+
+/// Multihash prefix of every CIDv0: sha2-256 with a 32 byte digest
+const SHA2_256_PREFIX: [u8; 2] = [0x12, 0x20];
+
+/// `PBNode.Data`
+const NODE_DATA: u64 = 1;
+/// `PBNode.Links`
+const NODE_LINKS: u64 = 2;
+/// `PBLink.Hash`
+const LINK_HASH: u64 = 1;
+/// `unixfs.Data.Type`
+const UNIXFS_TYPE: u64 = 1;
+/// `unixfs.Data.Data`
+const UNIXFS_DATA: u64 = 2;
+
+const UNIXFS_RAW: u64 = 0;
+const UNIXFS_FILE: u64 = 2;
+
+/// A decoded UnixFS file block: its inline data, followed in file order by
+/// the content of its child blocks.
+pub struct UnixfsNode<'a> {
+    pub data: &'a [u8],
+    pub links: Vec<String>,
+}
+
+/// Checks that `block` hashes to the CIDv0 `cid`.
+pub fn verify_block(cid: &str, block: &[u8]) -> Result<()> {
+    let multihash = bs58::decode(cid)
+        .into_vec()
+        .map_err(|_| anyhow!("Invalid IPFS hash '{}'", cid))?;
+    let digest = multihash
+        .strip_prefix(&SHA2_256_PREFIX)
+        .filter(|digest| digest.len() == 32)
+        .ok_or_else(|| anyhow!("Only CIDv0 (Qm...) hashes can be verified, got '{}'", cid))?;
+
+    if Sha256::digest(block).as_slice() != digest {
+        return Err(anyhow!("Content does not match hash {}", cid));
+    }
+    Ok(())
+}
+
+/// Decodes a dag-pb block holding a UnixFS file, or part of one.
+pub fn decode_file_node(block: &[u8]) -> Result<UnixfsNode<'_>> {
+    let mut unixfs = None;
+    let mut links = Vec::new();
+    for (field, value) in Fields::new(block) {
+        match (field, value?) {
+            (NODE_DATA, Field::Bytes(bytes)) => unixfs = Some(bytes),
+            (NODE_LINKS, Field::Bytes(link)) => links.push(link_cid(link)?),
+            _ => {}
+        }
+    }
+
+    let unixfs = unixfs.ok_or_else(|| anyhow!("Block is not a UnixFS node"))?;
+    let mut kind = None;
+    let mut data: &[u8] = &[];
+    for (field, value) in Fields::new(unixfs) {
+        match (field, value?) {
+            (UNIXFS_TYPE, Field::Varint(value)) => kind = Some(value),
+            (UNIXFS_DATA, Field::Bytes(bytes)) => data = bytes,
+            _ => {}
+        }
+    }
+
+    match kind {
+        Some(UNIXFS_FILE | UNIXFS_RAW) => Ok(UnixfsNode { data, links }),
+        _ => Err(anyhow!("Block is not a UnixFS file")),
+    }
+}
+
+fn link_cid(link: &[u8]) -> Result<String> {
+    for (field, value) in Fields::new(link) {
+        if let (LINK_HASH, Field::Bytes(hash)) = (field, value?) {
+            if hash.len() != 34 || !hash.starts_with(&SHA2_256_PREFIX) {
+                return Err(anyhow!("Only CIDv0 links can be verified"));
+            }
+            return Ok(bs58::encode(hash).into_string());
+        }
+    }
+    Err(anyhow!("UnixFS link has no hash"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `ipfs add` of "hello world", a single dag-pb / UnixFS file block
+    const HELLO_CID: &str = "Qmf412jQZiuVUtdgnB36FXFX7xg5V6KEbSJ4dpQuhkLyfD";
+    const HELLO_BLOCK: &[u8] = b"\x0a\x11\x08\x02\x12\x0bhello world\x18\x0b";
+
+    /// The empty UnixFS directory
+    const EMPTY_DIR_CID: &str = "QmUNLLsPACCz1vLxQVkXqqLX5R1X345qqfHbsf67hvA3Nn";
+    const EMPTY_DIR_BLOCK: &[u8] = b"\x0a\x02\x08\x01";
+
+    #[test]
+    fn verifies_known_blocks() {
+        verify_block(HELLO_CID, HELLO_BLOCK).unwrap();
+        verify_block(EMPTY_DIR_CID, EMPTY_DIR_BLOCK).unwrap();
+    }
+
+    #[test]
+    fn rejects_tampered_blocks() {
+        let mut tampered = HELLO_BLOCK.to_vec();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(verify_block(HELLO_CID, &tampered).is_err());
+        assert!(verify_block(HELLO_CID, EMPTY_DIR_BLOCK).is_err());
+        assert!(verify_block(HELLO_CID, &[]).is_err());
+    }
+
+    #[test]
+    fn rejects_hashes_that_are_not_cidv0() {
+        let error = verify_block(
+            "zb2rhe5P4gXftAwvA4eXQ5HJwsER2owDyS9sKaQRRVQPn93bA",
+            HELLO_BLOCK,
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("CIDv0"));
+        assert!(verify_block("not base58 0OIl", HELLO_BLOCK).is_err());
+    }
+
+    #[test]
+    fn decodes_file_data() {
+        let node = decode_file_node(HELLO_BLOCK).unwrap();
+        assert_eq!(node.data, b"hello world");
+        assert!(node.links.is_empty());
+    }
+
+    #[test]
+    fn decodes_file_links() {
+        let multihash = bs58::decode(HELLO_CID).into_vec().unwrap();
+        // PBNode { Links: [PBLink { Hash }], Data: unixfs.Data { Type: File } }
+        let mut block = vec![0x12, multihash.len() as u8 + 2, 0x0a, multihash.len() as u8];
+        block.extend(&multihash);
+        block.extend([0x0a, 0x02, 0x08, 0x02]);
+
+        let node = decode_file_node(&block).unwrap();
+        assert!(node.data.is_empty());
+        assert_eq!(node.links, vec![HELLO_CID.to_string()]);
+    }
+
+    #[test]
+    fn rejects_non_file_blocks() {
+        assert!(decode_file_node(EMPTY_DIR_BLOCK).is_err());
+        assert!(decode_file_node(b"hello world").is_err());
+        // Truncated: the Data field claims more bytes than remain
+        assert!(decode_file_node(&HELLO_BLOCK[..6]).is_err());
+    }
+}
@@ -1,7 +1,11 @@
 use colored::Colorize;
+use graph_networks_registry::Network;
 use std::collections::BTreeMap;
 
-use crate::models::{Indexer, IndexerPOI, POIGroup};
+use crate::models::{
+    AllocationStatus, ClosingPoiLookup, ClosingPoiStatus, DeploymentId, Indexer, IndexerDivergence,
+    IndexerPOI, LineageEntry, POIGroup, Reference,
+};
 
 pub fn display_header(title: &str) {
     println!("\n{}", "=".repeat(100).bright_cyan());
@@ -34,6 +38,25 @@ pub fn display_progress(message: &str) {
     print!("{} {}... ", "→".bright_cyan(), message);
 }
 
+/// Prints how many indexers were found, counting recently closed
+/// allocations separately.
+pub fn display_indexers_found(indexers: &BTreeMap<String, Indexer>) {
+    let closed = indexers
+        .values()
+        .filter(|indexer| indexer.allocation_status == AllocationStatus::Closed)
+        .count();
+    display_success(&format!(
+        "Found {} active indexers",
+        indexers.len() - closed
+    ));
+    if closed > 0 {
+        display_info(
+            "Recently closed allocations",
+            &format!("{} indexer(s)", closed),
+        );
+    }
+}
+
 pub fn display_pois(pois: Vec<IndexerPOI>, block: u32, deployment: &str) {
     display_header(&format!(
         "POIs for deployment {} at block {}",
@@ -80,8 +103,9 @@ pub fn display_pois(pois: Vec<IndexerPOI>, block: u32, deployment: &str) {
 
         // Table header
         println!(
-            "{:<44} │ {}",
+            "{:<44} │ {:<8} │ {}",
             " Indexer ID".bright_blue().bold(),
+            "Status".bright_blue().bold(),
             "URL".bright_blue().bold()
         );
         println!("{}", "─".repeat(100).bright_black());
@@ -94,15 +118,21 @@ pub fn display_pois(pois: Vec<IndexerPOI>, block: u32, deployment: &str) {
                 indexer.indexer_id.clone()
             };
 
-            let truncated_url = if indexer.indexer_url.len() > 52 {
-                format!("{}...", &indexer.indexer_url[..49])
+            let truncated_url = if indexer.indexer_url.len() > 41 {
+                format!("{}...", &indexer.indexer_url[..38])
             } else {
                 indexer.indexer_url.clone()
             };
 
+            let status = match indexer.allocation_status {
+                AllocationStatus::Active => indexer.allocation_status.to_string().green(),
+                AllocationStatus::Closed => indexer.allocation_status.to_string().yellow(),
+            };
+
             println!(
-                " {:<43} │ {}",
+                " {:<43} │ {:<8} │ {}",
                 truncated_id.white(),
+                status,
                 truncated_url.bright_black()
             );
         }
@@ -110,9 +140,16 @@ pub fn display_pois(pois: Vec<IndexerPOI>, block: u32, deployment: &str) {
     println!("\n{}", "═".repeat(100).bright_cyan());
 }
 
-pub fn display_poi_groups(groups: Vec<POIGroup>, block: u32, correct_indexer_id: &str) {
+pub fn display_poi_groups(
+    groups: Vec<POIGroup>,
+    indexers: &BTreeMap<String, Indexer>,
+    block: u32,
+    reference: &Reference,
+) {
     display_subheader(&format!("POI Groups at block {}", block));
 
+    let total_indexers: usize = groups.iter().map(|group| group.indexers.len()).sum();
+
     for group in groups {
         let status_icon = if group.is_correct {
             "✓".green().bold()
@@ -120,10 +157,15 @@ pub fn display_poi_groups(groups: Vec<POIGroup>, block: u32, correct_indexer_id:
             "✗".red().bold()
         };
 
-        let status_text = if group.is_correct {
-            "CORRECT".green().bold()
-        } else {
-            "DIVERGED".red().bold()
+        let has_reference_indexer = group
+            .indexers
+            .keys()
+            .any(|id| reference.is_reference_indexer(id));
+        let status_text = match (group.is_correct, reference) {
+            (true, Reference::Consensus { .. }) => "CONSENSUS".green().bold(),
+            (true, _) => "CORRECT".green().bold(),
+            (false, _) if has_reference_indexer => "CONFLICTING".yellow().bold(),
+            (false, _) => "DIVERGED".red().bold(),
         };
 
         println!(
@@ -133,15 +175,24 @@ pub fn display_poi_groups(groups: Vec<POIGroup>, block: u32, correct_indexer_id:
             group.poi.bright_white()
         );
 
-        println!("  {} ({}):", "Indexers".bright_blue(), group.indexers.len());
+        println!(
+            "  {} ({}/{}):",
+            "Indexers".bright_blue(),
+            group.indexers.len(),
+            total_indexers
+        );
 
         for indexer_id in group.indexers.keys() {
-            let marker = if indexer_id == correct_indexer_id {
+            let marker = if reference.is_reference_indexer(indexer_id) {
                 " (reference)".bright_magenta().to_string()
             } else {
                 String::new()
             };
-            println!("    • {}{}", indexer_id.white(), marker);
+            let status = match indexers.get(indexer_id).map(|i| i.allocation_status) {
+                Some(AllocationStatus::Closed) => " (closed)".yellow().to_string(),
+                _ => String::new(),
+            };
+            println!("    • {}{}{}", indexer_id.white(), marker, status);
         }
     }
 }
@@ -165,6 +216,233 @@ pub fn display_divergence_summary(
     }
 }
 
+pub fn display_indexer_divergences(
+    divergences: &[IndexerDivergence],
+    start_block: u32,
+    end_block: u32,
+) {
+    display_subheader(&format!(
+        "Per-indexer divergence between blocks {} and {}",
+        start_block, end_block
+    ));
+
+    println!(
+        "{:<44} │ {:<20} │ {:<20} │ {}",
+        " Indexer ID".bright_blue().bold(),
+        "Last match".bright_blue().bold(),
+        "First divergence".bright_blue().bold(),
+        "Status".bright_blue().bold()
+    );
+    println!("{}", "─".repeat(100).bright_black());
+
+    let format_block = |block: Option<u32>| block.map_or("-".to_string(), |b| b.to_string());
+
+    for divergence in divergences {
+        let status = match (divergence.resolved, divergence.first_divergence) {
+            (false, _) => "UNRESOLVED".yellow().bold(),
+            (true, Some(_)) => "DIVERGED".red().bold(),
+            (true, None) => "MATCH".green().bold(),
+        };
+
+        println!(
+            " {:<43} │ {:<20} │ {:<20} │ {}",
+            divergence.indexer_id.white(),
+            format_block(divergence.last_match),
+            format_block(divergence.first_divergence),
+            status
+        );
+    }
+
+    let diverged = divergences
+        .iter()
+        .filter(|divergence| divergence.resolved && divergence.first_divergence.is_some())
+        .count();
+    let unresolved: Vec<String> = divergences
+        .iter()
+        .filter(|divergence| !divergence.resolved)
+        .map(|divergence| divergence.indexer_id.clone())
+        .collect();
+
+    println!();
+    if diverged > 0 {
+        display_error(&format!(
+            "{} indexer(s) diverged from the reference",
+            diverged
+        ));
+    } else if unresolved.is_empty() {
+        display_success(&format!(
+            "No divergence found between blocks {} and {}",
+            start_block, end_block
+        ));
+    }
+    display_unresolved_indexers(&unresolved);
+}
+
+pub fn display_unresolved_indexers(indexer_ids: &[String]) {
+    if indexer_ids.is_empty() {
+        return;
+    }
+
+    display_warning(&format!(
+        "{} indexer(s) never returned a usable POI during the search:",
+        indexer_ids.len()
+    ));
+    for indexer_id in indexer_ids {
+        println!("  • {}", indexer_id.bright_black());
+    }
+}
+
+pub fn display_graft_lineage(lineage: &[LineageEntry]) {
+    if lineage.len() <= 1 {
+        return;
+    }
+
+    println!("\n{}", "Graft lineage:".bright_blue());
+    for entry in lineage {
+        match &entry.manifest.graft {
+            Some(graft) => println!(
+                "  • {} {}",
+                entry.deployment.white(),
+                format!("(grafted at block {})", graft.block).bright_black()
+            ),
+            None => println!(
+                "  • {} {}",
+                entry.deployment.white(),
+                "(root)".bright_black()
+            ),
+        }
+    }
+}
+
+pub fn display_closing_pois(lookups: &[ClosingPoiLookup]) {
+    display_subheader("Public POIs at Closing Blocks");
+
+    for lookup in lookups {
+        let (icon, label) = match &lookup.status {
+            ClosingPoiStatus::Matches => ("✓".green().bold(), "MATCHES".green().bold()),
+            ClosingPoiStatus::Diverged => ("✗".red().bold(), "DIVERGED".red().bold()),
+            ClosingPoiStatus::NotServed(_) => ("–".yellow().bold(), "NOT SERVED".yellow().bold()),
+            ClosingPoiStatus::Inconclusive(_) => {
+                ("?".yellow().bold(), "INCONCLUSIVE".yellow().bold())
+            }
+        };
+
+        let epoch = match (lookup.closed_at_epoch, lookup.block) {
+            (Some(epoch), Some(block)) => format!("epoch {} (block {})", epoch, block),
+            (Some(epoch), None) => format!("epoch {}", epoch),
+            _ => "unknown epoch".to_string(),
+        };
+
+        println!(
+            "\n{} {:<12} {} {}",
+            icon,
+            label,
+            lookup.allocation_id.white(),
+            epoch.bright_black()
+        );
+        println!("    {} {}", "Indexer:".bright_blue(), lookup.indexer_id);
+
+        if let ClosingPoiStatus::NotServed(reason) | ClosingPoiStatus::Inconclusive(reason) =
+            &lookup.status
+        {
+            println!("    {} {}", "Reason:".bright_blue(), reason.bright_black());
+        }
+
+        if lookup.status == ClosingPoiStatus::Diverged {
+            let missing = || "unavailable".bright_black().to_string();
+            println!(
+                "    {} {}",
+                "Public POI:   ".bright_blue(),
+                lookup.public_poi.clone().unwrap_or_else(missing)
+            );
+            println!(
+                "    {} {}",
+                "Consensus POI:".bright_blue(),
+                lookup.consensus_poi.clone().unwrap_or_else(missing)
+            );
+            println!(
+                "    {} {}",
+                "Closing POI:  ".bright_blue(),
+                lookup
+                    .closing_poi
+                    .clone()
+                    .unwrap_or_else(|| "none".bright_black().to_string())
+            );
+        }
+    }
+
+    let count = |status: &ClosingPoiStatus| {
+        lookups
+            .iter()
+            .filter(|lookup| &lookup.status == status)
+            .count()
+    };
+    let matches = count(&ClosingPoiStatus::Matches);
+    let diverged = count(&ClosingPoiStatus::Diverged);
+
+    println!();
+    display_info("Closed allocations", &lookups.len().to_string());
+    display_info("Matching consensus", &matches.to_string());
+    display_info(
+        "Closing POIs",
+        "not compared: they are derived with the indexer's address, public POIs with the zero address",
+    );
+    if diverged > 0 {
+        display_error(&format!(
+            "{} indexer(s) served a diverged public POI at the closing block; \
+             run check-divergence to find where they diverged",
+            diverged
+        ));
+    } else {
+        display_success("No diverged public POIs at closing blocks");
+    }
+}
+
+/// Lists registry networks as a table, with every RPC URL when only one
+/// network is shown.
+pub fn display_networks(networks: &[&Network]) {
+    if networks.is_empty() {
+        display_warning("No matching networks");
+        return;
+    }
+
+    println!(
+        "\n{:<24} │ {:<20} │ {:<30} │ {}",
+        " Network".bright_blue().bold(),
+        "CAIP-2 ID".bright_blue().bold(),
+        "Name".bright_blue().bold(),
+        "Aliases".bright_blue().bold()
+    );
+    println!("{}", "─".repeat(100).bright_black());
+    for network in networks {
+        let aliases = network.aliases.as_deref().unwrap_or_default().join(", ");
+        println!(
+            " {:<23} │ {:<20} │ {:<30} │ {}",
+            network.id.white(),
+            network.caip2_id.bright_black(),
+            network.full_name,
+            aliases.bright_black()
+        );
+    }
+
+    if let [network] = networks {
+        println!();
+        for url in network.rpc_urls.iter().flatten() {
+            display_info("RPC URL", url);
+        }
+    }
+    println!(
+        "\n{}",
+        format!("{} network(s)", networks.len()).bright_green()
+    );
+}
+
+/// Prints a deployment ID in both its IPFS hash and bytes32 forms.
+pub fn display_deployment(deployment: &DeploymentId) {
+    display_info("Deployment", deployment.ipfs_hash());
+    display_info("Deployment (bytes32)", &deployment.to_hex());
+}
+
 pub fn format_deployment_hash(hash: &str) -> String {
     if hash.len() > 16 {
         format!("{}...{}", &hash[..8], &hash[hash.len() - 8..])
@@ -176,7 +454,7 @@ pub fn format_deployment_hash(hash: &str) -> String {
 pub fn group_pois_by_hash(
     indexers: &BTreeMap<String, Indexer>,
     pois: &[(String, String)],
-    correct_indexer_id: &str,
+    reference_poi: Option<&str>,
 ) -> Vec<POIGroup> {
     let mut poi_groups: BTreeMap<String, BTreeMap<String, String>> = BTreeMap::new();
 
@@ -191,9 +469,126 @@ pub fn group_pois_by_hash(
     poi_groups
         .into_iter()
         .map(|(poi, indexers)| POIGroup {
-            is_correct: indexers.contains_key(correct_indexer_id),
+            is_correct: reference_poi == Some(poi.as_str()),
             poi,
             indexers,
         })
         .collect()
 }
+
+/// Returns the POI backed by a strict majority of the responding indexers,
+/// weighted by stake when `stake_weighted` is set. A plurality is not
+/// enough; without a majority the error says how large the biggest group is.
+pub fn find_consensus_poi(
+    indexers: &BTreeMap<String, Indexer>,
+    pois: &[(String, String)],
+    stake_weighted: bool,
+) -> Result<String, String> {
+    let mut weights: BTreeMap<&str, u128> = BTreeMap::new();
+    for (indexer_id, poi) in pois {
+        let weight = if stake_weighted {
+            indexers.get(indexer_id).map_or(0, Indexer::stake)
+        } else {
+            1
+        };
+        *weights.entry(poi).or_default() += weight;
+    }
+
+    let total: u128 = weights.values().sum();
+    let Some((poi, weight)) = weights.into_iter().max_by_key(|(_, weight)| *weight) else {
+        return Err("No responding indexers".to_string());
+    };
+    if weight > 0 && weight * 2 > total {
+        return Ok(poi.to_string());
+    }
+
+    Err(if stake_weighted {
+        format!(
+            "No majority POI: the largest group holds {}% of the responding stake",
+            (weight * 100).checked_div(total).unwrap_or(0)
+        )
+    } else {
+        format!(
+            "No majority POI: the largest group has {} of {} responding indexers",
+            weight, total
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn indexer(id: &str, stake: &str) -> (String, Indexer) {
+        let indexer = Indexer {
+            id: id.to_string(),
+            url: String::new(),
+            staked_tokens: Some(stake.to_string()),
+            allocation_ids: Vec::new(),
+            allocation_status: AllocationStatus::Active,
+        };
+        (id.to_string(), indexer)
+    }
+
+    fn pois(pois: &[(&str, &str)]) -> Vec<(String, String)> {
+        pois.iter()
+            .map(|(id, poi)| (id.to_string(), poi.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn consensus_needs_a_strict_majority() {
+        let indexers = BTreeMap::new();
+        let majority = pois(&[("a", "0x1"), ("b", "0x1"), ("c", "0x2")]);
+        assert_eq!(
+            find_consensus_poi(&indexers, &majority, false),
+            Ok("0x1".to_string())
+        );
+
+        let tie = pois(&[("a", "0x1"), ("b", "0x2")]);
+        assert!(find_consensus_poi(&indexers, &tie, false).is_err());
+    }
+
+    #[test]
+    fn plurality_is_reported_as_no_majority() {
+        let split = pois(&[
+            ("a", "0x1"),
+            ("b", "0x1"),
+            ("c", "0x2"),
+            ("d", "0x3"),
+            ("e", "0x3"),
+            ("f", "0x4"),
+        ]);
+        let reason = find_consensus_poi(&BTreeMap::new(), &split, false).unwrap_err();
+        assert_eq!(
+            reason,
+            "No majority POI: the largest group has 2 of 6 responding indexers"
+        );
+    }
+
+    #[test]
+    fn stake_weighted_consensus() {
+        let indexers: BTreeMap<String, Indexer> =
+            [indexer("a", "40"), indexer("b", "30"), indexer("c", "30")]
+                .into_iter()
+                .collect();
+
+        let split = pois(&[("a", "0x1"), ("b", "0x2"), ("c", "0x3")]);
+        let reason = find_consensus_poi(&indexers, &split, true).unwrap_err();
+        assert_eq!(
+            reason,
+            "No majority POI: the largest group holds 40% of the responding stake"
+        );
+
+        let majority = pois(&[("a", "0x1"), ("b", "0x1"), ("c", "0x3")]);
+        assert_eq!(
+            find_consensus_poi(&indexers, &majority, true),
+            Ok("0x1".to_string())
+        );
+    }
+
+    #[test]
+    fn no_responses_have_no_consensus() {
+        assert!(find_consensus_poi(&BTreeMap::new(), &[], false).is_err());
+    }
+}